@@ -0,0 +1,156 @@
+use checksum::{self, Algorithm, Checksum};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std;
+
+/// Directory of downloaded archives keyed by their strongest checksum.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EntryKind {
+    /// A complete archive.
+    Archive,
+    /// An interrupted download, resumed by the next download of the archive.
+    Partial,
+    /// The file locked while the archive is downloaded, left behind once it is complete.
+    Lock,
+}
+
+/// A file in the cache belonging to the archive with `checksum`.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub checksum: Checksum,
+    pub kind: EntryKind,
+    pub path: PathBuf,
+    pub size: u64,
+    /// When the archive was downloaded or last installed, or the partial download last written.
+    pub modified: SystemTime,
+}
+
+impl EntryKind {
+    fn suffix(&self) -> &'static str {
+        match *self {
+            EntryKind::Archive => ".zip",
+            EntryKind::Partial => ".zip.part",
+            EntryKind::Lock => ".zip.part.lock",
+        }
+    }
+}
+
+impl Cache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Cache {
+        Cache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `$ANDROID_SDK_CLI_CACHE_DIR`, `$XDG_CACHE_HOME/android-sdk-cli` or `~/.cache/android-sdk-cli`.
+    pub fn default_dir() -> Option<PathBuf> {
+        if let Some(dir) = std::env::var_os("ANDROID_SDK_CLI_CACHE_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        if let Some(dir) = std::env::var_os("XDG_CACHE_HOME") {
+            return Some(PathBuf::from(dir).join("android-sdk-cli"));
+        }
+        return std::env::home_dir().map(|home| home.join(".cache").join("android-sdk-cli"));
+    }
+
+    pub fn dir(&self) -> &Path {
+        return &self.dir;
+    }
+
+    fn archives_dir(&self) -> PathBuf {
+        return self.dir.join("archives");
+    }
+
+    pub fn path(&self, checksum: &Checksum) -> PathBuf {
+        return self.archives_dir().join(format!("{}{}", checksum.digest, EntryKind::Archive.suffix()));
+    }
+
+    /// Returns the cached archive with `checksum`, if any. The content is not verified.
+    pub fn get(&self, checksum: &Checksum) -> Option<PathBuf> {
+        let path = self.path(checksum);
+        if path.is_file() {
+            return Some(path);
+        }
+        return None;
+    }
+
+    /// Marks the cached archive with `checksum` as used now, so that cleaning the cache by age
+    /// keeps it.
+    pub fn touch(&self, checksum: &Checksum) -> std::io::Result<()> {
+        return OpenOptions::new().write(true).open(self.path(checksum)).and_then(|f| f.set_modified(SystemTime::now()));
+    }
+
+    /// Where a download of the archive with `checksum` is kept until it is complete, so that an
+    /// interrupted download can be resumed.
+    pub fn partial_path(&self, checksum: &Checksum) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(self.archives_dir())?;
+        return Ok(self.archives_dir().join(format!("{}{}", checksum.digest, EntryKind::Partial.suffix())));
+    }
+
+    /// Moves a verified archive into the cache.
+    pub fn insert(&self, checksum: &Checksum, file: &Path) -> std::io::Result<PathBuf> {
+        let path = self.path(checksum);
+        std::fs::create_dir_all(self.archives_dir())?;
+        std::fs::rename(file, &path)?;
+        return Ok(path);
+    }
+
+    pub fn remove(&self, checksum: &Checksum) -> std::io::Result<()> {
+        return std::fs::remove_file(self.path(checksum));
+    }
+
+    /// Lists the archives in the cache along with partial downloads and their locks.
+    pub fn entries(&self) -> std::io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        let read_dir = match std::fs::read_dir(self.archives_dir()) {
+            Ok(read_dir) => read_dir,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => { return Ok(entries); }
+            Err(e) => { return Err(e); }
+        };
+        for entry in read_dir {
+            let entry = entry?;
+            let path = entry.path();
+            let name = match path.file_name().and_then(|s| s.to_str()) {
+                Some(name) => name.to_string(),
+                None => { continue; }
+            };
+            let kind = match [EntryKind::Archive, EntryKind::Partial, EntryKind::Lock].iter().find(|k| name.ends_with(k.suffix())) {
+                Some(kind) => *kind,
+                None => { continue; }
+            };
+            // Files are named after the digest of their archive, whose length tells the algorithm.
+            let digest = &name[..name.len() - kind.suffix().len()];
+            let checksum = match Algorithm::from_digest(digest) {
+                Some(algorithm) => Checksum::new(algorithm, digest),
+                None => { continue; }
+            };
+            let metadata = entry.metadata()?;
+            entries.push(CacheEntry {
+                checksum: checksum,
+                kind: kind,
+                path: path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+        entries.sort_by(|a, b| (&a.checksum, a.kind).cmp(&(&b.checksum, b.kind)));
+        return Ok(entries);
+    }
+}
+
+impl CacheEntry {
+    /// Returns true if the content of the archive matches its checksum.
+    pub fn verify(&self) -> std::io::Result<bool> {
+        return checksum::verify_file(&self.path, std::slice::from_ref(&self.checksum));
+    }
+
+    pub fn remove(&self) -> std::io::Result<()> {
+        return std::fs::remove_file(&self.path);
+    }
+}
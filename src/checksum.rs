@@ -0,0 +1,160 @@
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use crypto::sha2::Sha256;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std;
+
+/// Hash functions archives are verified with, ordered from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Algorithm { Sha1, Sha256 }
+
+/// Expected digest of an archive.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    /// Lower-case hex digest.
+    pub digest: String,
+}
+
+/// Computes the digests of several algorithms in a single pass over the data.
+pub struct Hasher {
+    digests: Vec<(Algorithm, Box<dyn Digest>)>,
+}
+
+impl Algorithm {
+    pub fn all() -> &'static [Algorithm] {
+        static ALL: [Algorithm; 2] = [Algorithm::Sha1, Algorithm::Sha256];
+        return &ALL;
+    }
+
+    /// Name used in repository manifests, lockfiles and JSON output.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match *self {
+            Algorithm::Sha1 => "SHA-1",
+            Algorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    /// Guesses the algorithm of a hex digest from its length.
+    pub fn from_digest(digest: &str) -> Option<Algorithm> {
+        match digest.len() {
+            40 => Some(Algorithm::Sha1),
+            64 => Some(Algorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    fn digest(&self) -> Box<dyn Digest> {
+        match *self {
+            Algorithm::Sha1 => Box::new(Sha1::new()),
+            Algorithm::Sha256 => Box::new(Sha256::new()),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Algorithm, String> {
+        match s.to_lowercase().as_str() {
+            "sha1" | "sha-1" => Ok(Algorithm::Sha1),
+            "sha256" | "sha-256" => Ok(Algorithm::Sha256),
+            _ => Err(format!("unsupported checksum type: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.as_str());
+    }
+}
+
+impl Checksum {
+    pub fn new(algorithm: Algorithm, digest: &str) -> Checksum {
+        Checksum {
+            algorithm: algorithm,
+            digest: digest.trim().to_lowercase(),
+        }
+    }
+
+    /// The checksum of the strongest algorithm among `checksums`.
+    pub fn strongest(checksums: &[Checksum]) -> Option<&Checksum> {
+        return checksums.iter().max_by_key(|c| c.algorithm);
+    }
+}
+
+/// Formatted as `algorithm:digest`, e.g. `sha1:f4f1e0ee23ba5fd2eb7ba7e3e8f3eb3b1ce37d6f`.
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}:{}", self.algorithm, self.digest);
+    }
+}
+
+impl Hasher {
+    /// Computes a digest for each distinct algorithm of `checksums`.
+    pub fn new(checksums: &[Checksum]) -> Hasher {
+        let algorithms: Vec<Algorithm> = checksums.iter().map(|c| c.algorithm).collect();
+        return Hasher::with_algorithms(&algorithms);
+    }
+
+    pub fn with_algorithms(algorithms: &[Algorithm]) -> Hasher {
+        let mut algorithms = algorithms.to_vec();
+        algorithms.sort();
+        algorithms.dedup();
+        Hasher {
+            digests: algorithms.into_iter().map(|a| (a, a.digest())).collect(),
+        }
+    }
+
+    pub fn input(&mut self, data: &[u8]) {
+        for &mut (_, ref mut digest) in &mut self.digests {
+            digest.input(data);
+        }
+    }
+
+    pub fn input_reader<R: Read>(&mut self, reader: &mut R) -> std::io::Result<()> {
+        let mut buf = [0; 65536];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => { return Ok(()); }
+                len => { self.input(&buf[..len]); }
+            }
+        }
+    }
+
+    /// The digest of each algorithm, strongest first.
+    pub fn result(mut self) -> Vec<Checksum> {
+        self.digests.reverse();
+        return self.digests.iter_mut().map(|&mut (algorithm, ref mut digest)| Checksum::new(algorithm, &digest.result_str())).collect();
+    }
+
+    /// Compares the digests with `expected`, strongest algorithm first, and returns the first
+    /// expected checksum which does not match along with the actual one.
+    pub fn verify(self, expected: &[Checksum]) -> Result<(), (Checksum, Checksum)> {
+        for actual in self.result() {
+            if let Some(expected) = expected.iter().find(|c| c.algorithm == actual.algorithm && c.digest != actual.digest) {
+                return Err((expected.clone(), actual));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Returns true if the content of the file at `path` matches every checksum of `expected`.
+pub fn verify_file(path: &Path, expected: &[Checksum]) -> std::io::Result<bool> {
+    let mut hasher = Hasher::new(expected);
+    hasher.input_reader(&mut File::open(path)?)?;
+    return Ok(hasher.verify(expected).is_ok());
+}
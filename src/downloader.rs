@@ -1,50 +1,420 @@
-use crypto::digest::Digest;
-use crypto::sha1::Sha1;
+use cache::Cache;
+use checksum::{self, Algorithm, Checksum, Hasher};
+use error::Error;
+use http;
 use hyper;
+use lock::FileLock;
+use hyper::status::StatusCode;
+use retry::RetryPolicy;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std;
-use tempfile;
+use transport::{SchemeTransport, Request, Transport};
 
-#[derive(Debug)]
+/// Downloads and extracts archives. Clones share the same transport, so a downloader can be
+/// handed to worker threads.
+#[derive(Clone)]
 pub struct Downloader {
-    client: hyper::Client,
+    source: Source,
+    cache: Option<Cache>,
+    observer: Option<Arc<dyn ProgressObserver>>,
+    retry: RetryPolicy,
+    mirrors: Option<Mirrors>,
+    lock_timeout: Duration,
+}
+
+/// Servers holding the same archives as `base_url` under the same paths, tried in order when
+/// downloading an archive under `base_url` fails.
+#[derive(Debug, Clone)]
+pub struct Mirrors {
+    pub base_url: String,
+    pub urls: Vec<String>,
+}
+
+/// Receives the progress of downloads, from the thread running each download.
+pub trait ProgressObserver: Send + Sync {
+    /// Called each time a chunk of `progress.url` has been written.
+    fn progress(&self, progress: &Progress);
+
+    /// Called once the download of `url` has ended, successfully or not.
+    fn finished(&self, url: &str, success: bool);
+}
+
+#[derive(Debug, Clone)]
+pub struct Progress<'a> {
+    pub url: &'a str,
+    /// Bytes of the archive written so far, including those kept from an interrupted download.
+    pub downloaded: u64,
+    /// Size of the archive from `Content-Length` or the repository, if known.
+    pub total: Option<u64>,
+    /// Bytes received since this download started.
+    pub received: u64,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Transport(Arc<dyn Transport>),
+    Dir(Arc<ArchiveDir>),
+}
+
+/// A directory of archives, hashed once when the first archive is looked up in it.
+#[derive(Debug)]
+struct ArchiveDir {
+    path: PathBuf,
+    /// Path of the zip with each digest of every supported algorithm.
+    index: Mutex<Option<HashMap<Checksum, PathBuf>>>,
+}
+
+/// A verified archive on disk. Archives downloaded without a cache are removed when dropped.
+#[derive(Debug)]
+pub struct DownloadedArchive {
+    path: PathBuf,
+    temporary: bool,
 }
 
 impl Downloader {
-    pub fn new(client: hyper::Client) -> Downloader {
+    pub fn new(client: http::Client) -> Downloader {
+        return Downloader::with_transport(Arc::new(SchemeTransport::new(client)), None);
+    }
+
+    /// Creates a downloader which looks archives up in `cache` before downloading them and
+    /// stores downloaded archives into it.
+    pub fn with_cache(client: http::Client, cache: Cache) -> Downloader {
+        return Downloader::with_transport(Arc::new(SchemeTransport::new(client)), Some(cache));
+    }
+
+    pub fn with_transport(transport: Arc<dyn Transport>, cache: Option<Cache>) -> Downloader {
         Downloader {
-            client: client,
+            source: Source::Transport(transport),
+            cache: cache,
+            observer: None,
+            retry: RetryPolicy::default(),
+            mirrors: None,
+            lock_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Creates a downloader which never accesses the network and takes archives from `dir`
+    /// instead, matching them by checksum.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Downloader {
+        Downloader {
+            source: Source::Dir(Arc::new(ArchiveDir {
+                path: dir.as_ref().to_path_buf(),
+                index: Mutex::new(None),
+            })),
+            cache: None,
+            observer: None,
+            retry: RetryPolicy::default(),
+            mirrors: None,
+            lock_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Retries failed downloads according to `policy` instead of `RetryPolicy::default()`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Downloader {
+        self.retry = policy;
+        self
+    }
+
+    pub fn mirrors(mut self, mirrors: Mirrors) -> Downloader {
+        self.mirrors = Some(mirrors);
+        self
+    }
+
+    /// Waits up to `timeout` for another process downloading the same archive, instead of ten
+    /// minutes.
+    pub fn lock_timeout(mut self, timeout: Duration) -> Downloader {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Reports the progress of every download to `observer`. Archives found in the cache are
+    /// not reported.
+    pub fn observer<O: ProgressObserver + 'static>(mut self, observer: Arc<O>) -> Downloader {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn download(&self, url: &str, checksums: &[Checksum], dest_dir: &Path) -> Result<(), Error> {
+        let archive = self.fetch_archive(url, checksums, None)?;
+        return archive.extract(dest_dir);
+    }
+
+    /// Downloads the archive at `url` unless it is already available, and verifies it against
+    /// every one of `checksums`. The strongest checksum identifies the archive in the cache.
+    /// `size` is used for progress reports when the server does not send `Content-Length`.
+    pub fn fetch_archive(&self, url: &str, checksums: &[Checksum], size: Option<u64>) -> Result<DownloadedArchive, Error> {
+        let key = match Checksum::strongest(checksums) {
+            Some(checksum) => checksum,
+            None => { return Err(Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("no supported checksum for {}", url)))); }
+        };
+        let transport = match self.source {
+            Source::Transport(ref transport) => &**transport,
+            Source::Dir(ref dir) => {
+                return match dir.find(url, checksums)? {
+                    Some(path) => Ok(DownloadedArchive { path: path, temporary: false }),
+                    None => Err(Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no archive with checksum {} in {}: {}", key, dir.path.display(), url)))),
+                };
+            }
+        };
+
+        if let Some(archive) = self.cached_archive(key, checksums)? {
+            return Ok(archive);
+        }
+
+        let partial_path = match self.cache {
+            Some(ref cache) => cache.partial_path(key)?,
+            None => std::env::temp_dir().join(format!("android-sdk-cli-{}.zip.part", key.digest)),
+        };
+        // Keeps other processes and threads from appending to the same partial file. The lock is
+        // on a file of its own since the partial file is replaced when a download starts over.
+        let mut lock_path = partial_path.clone().into_os_string();
+        lock_path.push(".lock");
+        let _lock = FileLock::acquire(Path::new(&lock_path), self.lock_timeout, |_| {}).map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        // The archive may have been downloaded while waiting for the lock.
+        if let Some(archive) = self.cached_archive(key, checksums)? {
+            return Ok(archive);
+        }
+
+        let urls = match self.mirrors {
+            Some(ref mirrors) => mirrors.candidates(url),
+            None => vec![url.to_string()],
+        };
+        let mut errors = Vec::new();
+        for url in urls {
+            // Each attempt resumes from what the previous ones downloaded, even from another mirror.
+            let result = self.retry.run(|| fetch(transport, &url, checksums, size, &partial_path, self.observer.as_deref()));
+            if let Some(ref observer) = self.observer {
+                observer.finished(&url, result.is_ok());
+            }
+            match result {
+                Ok(()) => {
+                    errors.clear();
+                    break;
+                }
+                Err(e) => { errors.push((url, e)); }
+            }
+        }
+        if errors.len() == 1 {
+            return Err(errors.pop().unwrap().1);
+        } else if !errors.is_empty() {
+            return Err(Error::Mirrors(errors));
+        }
+        match self.cache {
+            Some(ref cache) => {
+                let path = cache.insert(key, &partial_path)?;
+                return Ok(DownloadedArchive { path: path, temporary: false });
+            }
+            None => {
+                return Ok(DownloadedArchive { path: partial_path, temporary: true });
+            }
+        }
+    }
+}
+
+impl Downloader {
+    fn cached_archive(&self, key: &Checksum, checksums: &[Checksum]) -> Result<Option<DownloadedArchive>, Error> {
+        if let Some(ref cache) = self.cache {
+            if let Some(path) = cache.get(key) {
+                if checksum::verify_file(&path, checksums)? {
+                    cache.touch(key)?;
+                    return Ok(Some(DownloadedArchive { path: path, temporary: false }));
+                }
+                // A corrupted archive is dropped and downloaded again.
+                cache.remove(key)?;
+            }
+        }
+        return Ok(None);
+    }
+}
+
+impl Mirrors {
+    /// `url` followed by the same resource on every mirror.
+    pub fn candidates(&self, url: &str) -> Vec<String> {
+        let mut urls = vec![url.to_string()];
+        let base_url = format!("{}/", self.base_url.trim_end_matches('/'));
+        if url.starts_with(&base_url) {
+            for mirror in &self.urls {
+                urls.push(format!("{}/{}", mirror.trim_end_matches('/'), &url[base_url.len()..]));
+            }
+        }
+        return urls;
+    }
+}
+
+impl fmt::Debug for Downloader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.debug_struct("Downloader").field("source", &self.source).field("cache", &self.cache).field("observer", &self.observer.is_some()).field("retry", &self.retry).field("mirrors", &self.mirrors).finish();
+    }
+}
+
+impl<'a> Progress<'a> {
+    /// Bytes per second received since this download started.
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 / 1e9;
+        if seconds <= 0.0 {
+            return 0.0;
         }
+        return self.received as f64 / seconds;
     }
 
-    pub fn download(&self, url: &str, expected_checksum: &str, dest_dir: &Path) -> Result<(), hyper::error::Error> {
-        let mut response = try!(self.client.get(url).send());
-        let mut zip_file = tempfile::NamedTempFile::new().unwrap();
-        let mut sha1 = Sha1::new();
+    /// Estimated time until the download completes at the current rate.
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate();
+        match self.total {
+            Some(total) if rate > 0.0 => Some(Duration::from_secs((total.saturating_sub(self.downloaded) as f64 / rate) as u64)),
+            _ => None,
+        }
+    }
+}
+
+impl DownloadedArchive {
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    pub fn extract(&self, dest_dir: &Path) -> Result<(), Error> {
+        return extract(&self.path, dest_dir);
+    }
+}
 
-        let mut buf = [0; 2048];
-        loop {
-            match response.read(&mut buf) {
-                Ok(0) => { break; }
-                Ok(len) => {
-                    let b = &buf[..len];
-                    let _ = zip_file.write_all(b);
-                    sha1.input(b);
+impl Drop for DownloadedArchive {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Downloads `url` into `partial_path`, resuming from the bytes already in it when the server
+/// supports range requests. The partial file is kept on network errors so that the next
+/// attempt can resume, and removed when the complete archive does not match its checksum.
+/// A resumed download which does not match is downloaded again from the start.
+fn fetch(transport: &dyn Transport, url: &str, checksums: &[Checksum], size: Option<u64>, partial_path: &Path, observer: Option<&dyn ProgressObserver>) -> Result<(), Error> {
+    let started = Instant::now();
+    // Reads start at the beginning of the file while writes are appended to its end.
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(partial_path)?;
+    let offset = file.metadata()?.len();
+    let mut hasher = Hasher::new(checksums);
+
+    let mut request = Request::new(url);
+    if offset > 0 {
+        request.range_from = Some(offset);
+    }
+    let mut response = match transport.get(&request) {
+        Err(Error::Status { status: StatusCode::RangeNotSatisfiable, .. }) if offset > 0 => {
+            // The partial file may hold the whole archive already, e.g. when the process was
+            // interrupted before moving it into the cache.
+            if checksum::verify_file(partial_path, checksums)? {
+                return Ok(());
+            }
+            // Otherwise it is not a prefix of the archive; start over. Open files cannot be
+            // removed on Windows.
+            drop(file);
+            std::fs::remove_file(partial_path)?;
+            return fetch(transport, url, checksums, size, partial_path, observer);
+        }
+        result => result?,
+    };
+    let mut resumed = false;
+    match response.status {
+        StatusCode::PartialContent if offset > 0 && response.range_start == Some(offset) => {
+            hasher.input_reader(&mut file)?;
+            resumed = true;
+        }
+        _ => {
+            // The server ignored the range and sends the whole archive.
+            file.set_len(0)?;
+        }
+    }
+
+    let mut downloaded = if resumed { offset } else { 0 };
+    let total = match response.content_length {
+        Some(len) => Some(downloaded + len),
+        None => size,
+    };
+    let mut buf = [0; 65536];
+    loop {
+        match response.body.read(&mut buf) {
+            Ok(0) => { break; }
+            Ok(len) => {
+                let b = &buf[..len];
+                file.write_all(b)?;
+                hasher.input(b);
+                downloaded += len as u64;
+                if let Some(observer) = observer {
+                    observer.progress(&Progress {
+                        url: url,
+                        downloaded: downloaded,
+                        total: total,
+                        received: downloaded - if resumed { offset } else { 0 },
+                        elapsed: started.elapsed(),
+                    });
                 }
-                Err(e) => { return Err(hyper::error::Error::Io(e)); }
             }
+            Err(e) => { return Err(Error::Http(hyper::error::Error::Io(e))); }
         }
-        let actual_checksum = sha1.result_str();
-        if actual_checksum != expected_checksum {
-            return Err(hyper::error::Error::Io(std::io::Error::new(std::io::ErrorKind::Other, format!("Checksum failure: {}\nExpected: {}\nActual : {}", url, expected_checksum, actual_checksum))));
+    }
+    if let Err((expected, actual)) = hasher.verify(checksums) {
+        drop(file);
+        std::fs::remove_file(partial_path)?;
+        if resumed {
+            // The bytes downloaded before were not a prefix of this archive.
+            return fetch(transport, url, checksums, size, partial_path, observer);
         }
+        return Err(Error::Checksum { url: url.to_string(), expected: expected.to_string(), actual: actual.to_string() });
+    }
+    return Ok(());
+}
+
+impl ArchiveDir {
+    /// Looks for the archive matching `checksums`, trying the file named like the last
+    /// component of `url` before the digests of every zip in the directory.
+    fn find(&self, url: &str, checksums: &[Checksum]) -> std::io::Result<Option<PathBuf>> {
+        if let Some(name) = url.rsplit('/').next() {
+            let path = self.path.join(name);
+            if path.is_file() && checksum::verify_file(&path, checksums)? {
+                return Ok(Some(path));
+            }
+        }
+        let mut index = self.index.lock().unwrap();
+        if index.is_none() {
+            *index = Some(index_dir(&self.path)?);
+        }
+        let index = index.as_ref().unwrap();
+        let found = checksums.first().and_then(|c| index.get(c));
+        return Ok(found.filter(|path| checksums.iter().all(|c| index.get(c) == Some(*path))).cloned());
+    }
+}
 
-        // TODO: Extract zip in Rust
-        match std::process::Command::new("unzip").arg("-q").arg("-f").arg("-d").arg(dest_dir).arg(zip_file.path()).spawn().and_then(|mut child| child.wait()) {
-            Ok(_) => {}
-            Err(e) => { return Err(hyper::error::Error::Io(e)); }
+/// Maps the digests of every supported algorithm of each zip in `dir` to its path.
+fn index_dir(dir: &Path) -> std::io::Result<HashMap<Checksum, PathBuf>> {
+    let mut index = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map(|e| e == "zip").unwrap_or(false) {
+            let mut hasher = Hasher::with_algorithms(Algorithm::all());
+            hasher.input_reader(&mut File::open(&path)?)?;
+            for checksum in hasher.result() {
+                index.insert(checksum, path.clone());
+            }
         }
-        return Ok(());
     }
+    return Ok(index);
+}
+
+fn extract(zip_path: &Path, dest_dir: &Path) -> Result<(), Error> {
+    // TODO: Extract zip in Rust
+    match std::process::Command::new("unzip").arg("-q").arg("-o").arg("-d").arg(dest_dir).arg(zip_path).spawn().and_then(|mut child| child.wait()) {
+        Ok(ref status) if status.success() => {}
+        Ok(status) => { return Err(Error::Io(std::io::Error::other(format!("unzip failed: {}: {}", zip_path.display(), status)))); }
+        Err(e) => { return Err(Error::Io(e)); }
+    }
+    return Ok(());
 }
@@ -0,0 +1,127 @@
+use hyper;
+use hyper::status::StatusCode;
+use std::error;
+use std::fmt;
+use std;
+
+/// Errors of downloading archives and manifests.
+#[derive(Debug)]
+pub enum Error {
+    /// The request failed or the connection broke while reading the response.
+    Http(hyper::error::Error),
+    /// A local file could not be read or written.
+    Io(std::io::Error),
+    /// The server responded with an error status to a request for `url`, the URL reached after
+    /// following redirects.
+    Status { url: String, status: StatusCode },
+    /// The redirect limit was exceeded; `url` is the last URL redirected from.
+    TooManyRedirects(String),
+    Checksum { url: String, expected: String, actual: String },
+    /// The last error of an operation which was attempted more than once.
+    Attempts(u32, Box<Error>),
+    /// The errors of downloading from each mirror, keyed by URL.
+    Mirrors(Vec<(String, Error)>),
+}
+
+impl Error {
+    /// Returns true if the error may go away by trying again: network failures and temporary
+    /// error statuses.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Http(hyper::error::Error::Io(_)) => true,
+            Error::Status { status, .. } => is_retryable_status(status),
+            _ => false,
+        }
+    }
+}
+
+/// Statuses servers use for temporary conditions.
+fn is_retryable_status(status: StatusCode) -> bool {
+    return matches!(status, StatusCode::RequestTimeout | StatusCode::TooManyRequests | StatusCode::InternalServerError | StatusCode::BadGateway | StatusCode::ServiceUnavailable | StatusCode::GatewayTimeout);
+}
+
+impl From<hyper::error::Error> for Error {
+    fn from(e: hyper::error::Error) -> Error {
+        Error::Http(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Http(ref e) => write!(f, "{}", e),
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Status { ref url, status } => write!(f, "{}: server responded with {}", url, status),
+            Error::TooManyRedirects(ref url) => write!(f, "{}: too many redirects", url),
+            Error::Checksum { ref url, ref expected, ref actual } => write!(f, "Checksum failure: {}\nExpected: {}\nActual : {}", url, expected, actual),
+            Error::Attempts(attempts, ref e) => write!(f, "{} (after {} attempts)", e, attempts),
+            Error::Mirrors(ref errors) => {
+                write!(f, "all mirrors failed:")?;
+                for (url, e) in errors {
+                    let message = e.to_string();
+                    if message.starts_with(url.as_str()) {
+                        write!(f, "\n  {}", message)?;
+                    } else {
+                        write!(f, "\n  {}: {}", url, message)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Http(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Attempts(_, ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use hyper;
+    use hyper::status::StatusCode;
+    use std;
+
+    fn status(status: StatusCode) -> Error {
+        return Error::Status { url: "https://repo.example.com/android/repository-11.xml".to_string(), status: status };
+    }
+
+    #[test]
+    fn retry_temporary_failures() {
+        for &code in &[StatusCode::RequestTimeout, StatusCode::TooManyRequests, StatusCode::InternalServerError, StatusCode::BadGateway, StatusCode::ServiceUnavailable, StatusCode::GatewayTimeout] {
+            assert!(status(code).is_retryable(), "{}", code);
+        }
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+        assert!(Error::Http(hyper::error::Error::Io(reset)).is_retryable());
+    }
+
+    #[test]
+    fn fail_fast_on_permanent_errors() {
+        for &code in &[StatusCode::BadRequest, StatusCode::Unauthorized, StatusCode::Forbidden, StatusCode::NotFound, StatusCode::RangeNotSatisfiable, StatusCode::NotImplemented, StatusCode::HttpVersionNotSupported] {
+            assert!(!status(code).is_retryable(), "{}", code);
+        }
+        let errors = vec![
+            Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file")),
+            Error::Http(hyper::error::Error::Method),
+            Error::TooManyRedirects("https://repo.example.com/android".to_string()),
+            Error::Checksum { url: String::new(), expected: String::new(), actual: String::new() },
+            Error::Attempts(4, Box::new(status(StatusCode::ServiceUnavailable))),
+        ];
+        for error in &errors {
+            assert!(!error.is_retryable(), "{}", error);
+        }
+    }
+}
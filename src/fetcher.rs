@@ -0,0 +1,193 @@
+use cache::Cache;
+use downloader::Mirrors;
+use error::Error;
+use fshelper;
+use hyper;
+use hyper::status::StatusCode;
+use local;
+use retry::RetryPolicy;
+use repository11::{self, SdkRepository};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std;
+use transport::{Request, Response, Transport};
+use xmlhelper;
+
+/// Fetches repository manifests, keeping the last one in the cache and revalidating it with
+/// conditional requests once it is older than `max_age`.
+#[derive(Debug)]
+pub struct Fetcher<'a> {
+    transport: &'a dyn Transport,
+    cache: Option<Cache>,
+    max_age: Duration,
+    offline: bool,
+    retry: RetryPolicy,
+    mirrors: Option<Mirrors>,
+}
+
+struct CachedManifest {
+    body_path: PathBuf,
+    meta_path: PathBuf,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: Option<SystemTime>,
+}
+
+impl<'a> Fetcher<'a> {
+    pub fn new(transport: &'a dyn Transport, cache: Option<Cache>) -> Fetcher<'a> {
+        Fetcher {
+            transport: transport,
+            cache: cache,
+            max_age: Duration::from_secs(60 * 60),
+            offline: false,
+            retry: RetryPolicy::default(),
+            mirrors: None,
+        }
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Fetcher<'a> {
+        self.retry = policy;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Fetcher<'a> {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Uses the cached manifest only, without touching the network.
+    pub fn offline(mut self, offline: bool) -> Fetcher<'a> {
+        self.offline = offline;
+        self
+    }
+
+    /// Fetches the manifest from each mirror in order when it cannot be fetched from the
+    /// repository.
+    pub fn mirrors(mut self, mirrors: Mirrors) -> Fetcher<'a> {
+        self.mirrors = Some(mirrors);
+        self
+    }
+
+    pub fn fetch_repository(&self) -> Result<SdkRepository, String> {
+        return self.fetch(repository11::XML_URL);
+    }
+
+    /// Fetches the manifest at `url`, whose relative archive URLs are resolved against the
+    /// directory of `url`. This holds for a manifest fetched from a mirror too, since archives
+    /// fall back to the mirrors on their own.
+    pub fn fetch(&self, url: &str) -> Result<SdkRepository, String> {
+        let urls = match self.mirrors {
+            Some(ref mirrors) => mirrors.candidates(url),
+            None => vec![url.to_string()],
+        };
+        let mut errors = Vec::new();
+        for candidate in &urls {
+            match self.fetch_manifest(candidate) {
+                Ok(mut sdk_repository) => {
+                    sdk_repository.resolve_urls(&url[..url.rfind('/').unwrap_or(0)]);
+                    return Ok(sdk_repository);
+                }
+                Err(e) => { errors.push((candidate, e)); }
+            }
+        }
+        if errors.len() == 1 {
+            return Err(errors.pop().unwrap().1);
+        }
+        let errors: Vec<String> = errors.into_iter().map(|(url, e)| format!("\n  {}: {}", url, e)).collect();
+        return Err(format!("all mirrors failed:{}", errors.concat()));
+    }
+
+    fn fetch_manifest(&self, url: &str) -> Result<SdkRepository, String> {
+        // Local manifests are always read directly, even when offline.
+        let cached = if url.starts_with("file:") { None } else { self.cache.as_ref().map(|cache| read_cached_manifest(cache, url)) };
+        let cached = match cached {
+            Some(cached) => cached,
+            None => {
+                if self.offline && !url.starts_with("file:") {
+                    return Err("cannot use --offline without a cache directory".to_string());
+                }
+                let (_, body) = self.get(&Request::new(url)).map_err(|e| e.to_string())?;
+                return parse(&body[..]);
+            }
+        };
+
+        let fresh = cached.fetched_at.and_then(|t| t.elapsed().ok()).map(|age| age <= self.max_age).unwrap_or(false);
+        if self.offline || fresh {
+            return match File::open(&cached.body_path) {
+                Ok(file) => parse(file),
+                Err(_) if self.offline => Err(format!("no cached manifest for {}; run once without --offline", url)),
+                Err(e) => Err(format!("failed to read {}: {}", cached.body_path.display(), e)),
+            };
+        }
+
+        let mut request = Request::new(url);
+        if cached.body_path.is_file() {
+            request.if_none_match = cached.etag.clone();
+            request.if_modified_since = cached.last_modified.clone();
+        }
+        let (response, body) = self.get(&request).map_err(|e| e.to_string())?;
+        if response.status == StatusCode::NotModified {
+            write_meta(&cached.meta_path, cached.etag.as_ref(), cached.last_modified.as_ref())?;
+            let file = File::open(&cached.body_path).map_err(|e| format!("failed to read {}: {}", cached.body_path.display(), e))?;
+            return parse(file);
+        }
+
+        let sdk_repository = parse(&body[..])?;
+        // The manifest is cached only once it is known to parse.
+        fshelper::write_atomically(&cached.body_path, &body).map_err(|e| format!("failed to write {}: {}", cached.body_path.display(), e))?;
+        write_meta(&cached.meta_path, response.etag.as_ref(), response.last_modified.as_ref())?;
+        return Ok(sdk_repository);
+    }
+
+    /// Sends a GET request and reads the whole response, retrying failures.
+    fn get(&self, request: &Request) -> Result<(Response, Vec<u8>), Error> {
+        return self.retry.run(|| {
+            let mut response = self.transport.get(request)?;
+            let mut body = Vec::new();
+            response.body.read_to_end(&mut body).map_err(|e| Error::Http(hyper::error::Error::Io(e)))?;
+            return Ok((response, body));
+        });
+    }
+}
+
+/// Reads a manifest from a local file.
+pub fn load_repository(path: &Path) -> Result<SdkRepository, String> {
+    let file = File::open(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    return parse(file).map_err(|e| format!("{}: {}", path.display(), e));
+}
+
+fn parse<R: Read>(source: R) -> Result<SdkRepository, String> {
+    let stream = xmlhelper::parse(source).map_err(|e| e.to_string())?;
+    return repository11::parse_sdk_repository(&mut stream.into_iter());
+}
+
+fn read_cached_manifest(cache: &Cache, url: &str) -> CachedManifest {
+    let dir = cache.dir().join("manifests");
+    let _ = std::fs::create_dir_all(&dir);
+    let name: String = url.chars().map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    let body_path = dir.join(format!("{}.xml", name));
+    let meta_path = dir.join(format!("{}.properties", name));
+    let meta = local::read_properties(&meta_path).unwrap_or_default();
+    CachedManifest {
+        body_path: body_path,
+        meta_path: meta_path,
+        etag: meta.get("etag").cloned(),
+        last_modified: meta.get("last-modified").cloned(),
+        fetched_at: meta.get("fetched-at").and_then(|s| s.parse::<u64>().ok()).map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+    }
+}
+
+fn write_meta(path: &Path, etag: Option<&String>, last_modified: Option<&String>) -> Result<(), String> {
+    let mut content = String::new();
+    if let Some(etag) = etag {
+        content.push_str(&format!("etag={}\n", etag));
+    }
+    if let Some(last_modified) = last_modified {
+        content.push_str(&format!("last-modified={}\n", last_modified));
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    content.push_str(&format!("fetched-at={}\n", now));
+    return fshelper::write_atomically(path, content.as_bytes()).map_err(|e| format!("failed to write {}: {}", path.display(), e));
+}
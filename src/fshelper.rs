@@ -0,0 +1,20 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std;
+
+/// Writes `content` to `path` through a temporary file next to it, which is renamed into place
+/// once complete, so that an interrupted write never leaves a truncated file behind.
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut temp_path = path.as_os_str().to_os_string();
+    temp_path.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = PathBuf::from(temp_path);
+    let result = File::create(&temp_path).and_then(|mut f| {
+        f.write_all(content)?;
+        return f.sync_all();
+    }).and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    return result;
+}
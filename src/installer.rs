@@ -0,0 +1,239 @@
+use downloader::{DownloadedArchive, Downloader};
+use local::{self, LocalSdk, SOURCE_PROPERTIES};
+use package::Package;
+use package_id::PackageId;
+use repository11::{self, Archive};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std;
+
+// Directories in the SDK root a package is extracted into, and its installed revision moved
+// to, while it is installed. Both are followed by the package id.
+static STAGING_PREFIX: &'static str = ".staging-";
+static BACKUP_PREFIX: &'static str = ".backup-";
+
+pub struct Installer<'a> {
+    downloader: &'a Downloader,
+    local_sdk: &'a LocalSdk,
+    base_url: String,
+}
+
+impl<'a> Installer<'a> {
+    pub fn new(downloader: &'a Downloader, local_sdk: &'a LocalSdk) -> Installer<'a> {
+        Installer {
+            downloader: downloader,
+            local_sdk: local_sdk,
+            base_url: repository11::XML_URL_BASE.to_string(),
+        }
+    }
+
+    /// Sets the URL relative archive URLs, such as those of a lockfile, are resolved against.
+    pub fn base_url(mut self, base_url: &str) -> Installer<'a> {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// Downloads the archive of `package` for the running host and installs it into its
+    /// install path, replacing any installed revision.
+    pub fn install(&self, package: &dyn Package) -> Result<(), String> {
+        let archive = self.fetch_archive(package)?;
+        return self.install_archive(package, &archive);
+    }
+
+    /// Installs `packages` in order, downloading up to `jobs` archives concurrently.
+    /// Extraction and installation stay sequential; `installed` is called after each package.
+    /// When a download fails, the packages before it are still installed and the ones after it
+    /// are downloaded but not installed; the errors of every failed download are reported
+    /// together.
+    pub fn install_all<F: FnMut(&dyn Package)>(&self, packages: &[&dyn Package], jobs: usize, mut installed: F) -> Result<(), String> {
+        let mut queue = VecDeque::new();
+        for (i, package) in packages.iter().enumerate() {
+            let id = package.id();
+            match package.archive_for_host() {
+                Some(archive) => queue.push_back((i, id, archive.url_relative_to(&self.base_url), archive.checksums.clone(), known_size(archive))),
+                None => { return Err(format!("{} has no archive for this host", id)); }
+            }
+        }
+        let queue = Arc::new(Mutex::new(queue));
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..std::cmp::max(1, std::cmp::min(jobs, packages.len())) {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            let downloader = self.downloader.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = queue.lock().unwrap().pop_front();
+                    let (i, id, url, checksum, size) = match job {
+                        Some(job) => job,
+                        None => { break; }
+                    };
+                    let result = downloader.fetch_archive(&url, &checksum, size).map_err(|e| format!("{}: {}", id, e));
+                    if sender.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut downloaded: BTreeMap<usize, DownloadedArchive> = BTreeMap::new();
+        let mut next = 0;
+        // Index of the first package which failed to download or install. Packages after it
+        // may depend on it and are not installed.
+        let mut first_failed = packages.len();
+        let mut errors = Vec::new();
+        for (i, result) in receiver.iter() {
+            match result {
+                Ok(archive) => { downloaded.insert(i, archive); }
+                Err(e) => {
+                    errors.push(e);
+                    first_failed = std::cmp::min(first_failed, i);
+                }
+            }
+            while next < first_failed {
+                let archive = match downloaded.remove(&next) {
+                    Some(archive) => archive,
+                    None => { break; }
+                };
+                if let Err(e) = self.install_archive(packages[next], &archive) {
+                    errors.push(e);
+                    first_failed = next;
+                    break;
+                }
+                installed(packages[next]);
+                next += 1;
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+        return Ok(());
+    }
+
+    fn fetch_archive(&self, package: &dyn Package) -> Result<DownloadedArchive, String> {
+        let archive = match package.archive_for_host() {
+            Some(archive) => archive,
+            None => { return Err(format!("{} has no archive for this host", package.id())); }
+        };
+        return self.downloader.fetch_archive(&archive.url_relative_to(&self.base_url), &archive.checksums, known_size(archive)).map_err(|e| e.to_string());
+    }
+
+    /// Extracts `archive` into a staging directory next to the install path, checks that it
+    /// holds a package, and only then moves it into place. The installed revision, if any,
+    /// is kept aside until the new one is in place and restored if that fails.
+    fn install_archive(&self, package: &dyn Package, archive: &DownloadedArchive) -> Result<(), String> {
+        let id = package.id();
+        let staging_dir = self.local_sdk.root().join(format!("{}{}", STAGING_PREFIX, id));
+        let backup_dir = self.local_sdk.root().join(format!("{}{}", BACKUP_PREFIX, id));
+        let dest = self.local_sdk.package_path(&id);
+        restore_backup(&dest, &backup_dir)?;
+
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir).map_err(|e| format!("failed to remove {}: {}", staging_dir.display(), e))?;
+        }
+        std::fs::create_dir_all(&staging_dir).map_err(|e| format!("failed to create {}: {}", staging_dir.display(), e))?;
+        let result = stage(archive, &staging_dir).and_then(|content_dir| replace(&content_dir, &dest, &backup_dir));
+        let _ = std::fs::remove_dir_all(&staging_dir);
+        return result;
+    }
+}
+
+/// Cleans up after installations and removals interrupted in `local_sdk`: the backup of each
+/// package is restored or removed, and staging directories are removed. Call it while holding
+/// the lock on the SDK root, so that no other process is installing packages.
+pub fn recover(local_sdk: &LocalSdk) -> Result<(), String> {
+    let root = local_sdk.root();
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => { return Ok(()); }
+        Err(e) => { return Err(format!("failed to read {}: {}", root.display(), e)); }
+    };
+    for entry in entries {
+        let path = entry.map_err(|e| format!("failed to read {}: {}", root.display(), e))?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => { continue; }
+        };
+        if let Some(id) = name.strip_prefix(BACKUP_PREFIX) {
+            // Directories which are not named after a package are left alone.
+            if let Ok(id) = PackageId::from_str(id) {
+                restore_backup(&local_sdk.package_path(&id), &path)?;
+            }
+        } else if name.starts_with(STAGING_PREFIX) || name.starts_with(local::REMOVING_PREFIX) {
+            std::fs::remove_dir_all(&path).map_err(|e| format!("failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    return Ok(());
+}
+
+/// Completes or undoes an installation interrupted between moving the installed revision
+/// aside and removing it.
+fn restore_backup(dest: &Path, backup_dir: &Path) -> Result<(), String> {
+    if !backup_dir.exists() {
+        return Ok(());
+    }
+    if dest.exists() {
+        return std::fs::remove_dir_all(backup_dir).map_err(|e| format!("failed to remove {}: {}", backup_dir.display(), e));
+    }
+    return std::fs::rename(backup_dir, dest).map_err(|e| format!("failed to restore {} from {}: {}", dest.display(), backup_dir.display(), e));
+}
+
+/// Extracts `archive` into `staging_dir` and returns the directory holding the package.
+fn stage(archive: &DownloadedArchive, staging_dir: &Path) -> Result<PathBuf, String> {
+    archive.extract(staging_dir).map_err(|e| e.to_string())?;
+    let content_dir = content_dir(staging_dir)?;
+    // Without it the package would not be recognized as installed.
+    if !content_dir.join(SOURCE_PROPERTIES).is_file() {
+        return Err(format!("{}: archive does not contain {}", archive.path().display(), SOURCE_PROPERTIES));
+    }
+    return Ok(content_dir);
+}
+
+/// Renames `content_dir` to `dest`, moving any existing `dest` to `backup_dir` first and
+/// back if the rename fails.
+fn replace(content_dir: &Path, dest: &Path, backup_dir: &Path) -> Result<(), String> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+    }
+    let replacing = dest.exists();
+    if replacing {
+        std::fs::rename(dest, backup_dir).map_err(|e| format!("failed to move {} to {}: {}", dest.display(), backup_dir.display(), e))?;
+    }
+    if let Err(e) = std::fs::rename(content_dir, dest) {
+        let mut message = format!("failed to move {} to {}: {}", content_dir.display(), dest.display(), e);
+        if replacing {
+            if let Err(e) = std::fs::rename(backup_dir, dest) {
+                message.push_str(&format!("\nfailed to restore {} from {}: {}", dest.display(), backup_dir.display(), e));
+            }
+        }
+        return Err(message);
+    }
+    if replacing {
+        let _ = std::fs::remove_dir_all(backup_dir);
+    }
+    return Ok(());
+}
+
+// Repositories omitting <size> leave it at 0.
+fn known_size(archive: &Archive) -> Option<u64> {
+    if archive.size > 0 {
+        return Some(archive.size);
+    }
+    return None;
+}
+
+// Archives usually contain a single top-level directory (e.g. android-6.0/) holding the package.
+fn content_dir(extracted_dir: &Path) -> Result<PathBuf, String> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(extracted_dir).map_err(|e| format!("failed to read {}: {}", extracted_dir.display(), e))? {
+        entries.push(entry.map_err(|e| format!("failed to read {}: {}", extracted_dir.display(), e))?);
+    }
+    if entries.len() == 1 && entries[0].path().is_dir() {
+        return Ok(entries[0].path());
+    }
+    return Ok(extracted_dir.to_path_buf());
+}
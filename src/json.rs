@@ -0,0 +1,121 @@
+use local::InstalledPackage;
+use package::Package;
+use package_id::PackageId;
+use resolver::{Plan, Step};
+use repository11::{Archive, BitSize, Revision, SdkRepository};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+
+impl ToJson for Revision {
+    fn to_json(&self) -> Json {
+        return Json::String(self.to_string());
+    }
+}
+
+impl ToJson for Archive {
+    fn to_json(&self) -> Json {
+        let mut checksums = BTreeMap::new();
+        for checksum in &self.checksums {
+            checksums.insert(checksum.algorithm.as_str().to_string(), checksum.digest.to_json());
+        }
+
+        let mut object = BTreeMap::new();
+        object.insert("url".to_string(), self.absolute_url().to_json());
+        object.insert("size".to_string(), self.size.to_json());
+        object.insert("checksums".to_string(), Json::Object(checksums));
+        object.insert("host_os".to_string(), self.host_os.map(|os| os.as_str().to_string()).to_json());
+        object.insert("host_bits".to_string(), self.host_bits.map(|bits| match bits {
+            BitSize::Bit32 => 32,
+            BitSize::Bit64 => 64,
+        }).to_json());
+        return Json::Object(object);
+    }
+}
+
+fn package_type(id: &PackageId) -> &'static str {
+    match *id {
+        PackageId::NdkBundle => "ndk",
+        PackageId::PlatformTools => "platform-tool",
+        PackageId::Tools => "tool",
+        PackageId::Platform(_) => "platform",
+        PackageId::Source(_) => "source",
+        PackageId::BuildTools(_) => "build-tool",
+        PackageId::SystemImage { .. } => "system-image",
+        PackageId::AddOn { .. } => "add-on",
+    }
+}
+
+fn min_revision(package: &dyn Package, dependency: PackageId) -> Option<Revision> {
+    return package.dependencies().into_iter().find(|d| d.id == dependency).and_then(|d| d.min_revision);
+}
+
+/// Converts `package` to a package object, to which callers may add fields of their own.
+pub fn package(package: &dyn Package) -> BTreeMap<String, Json> {
+    let id = package.id();
+    let mut object = BTreeMap::new();
+    object.insert("id".to_string(), id.to_string().to_json());
+    object.insert("type".to_string(), package_type(&id).to_json());
+    object.insert("display_name".to_string(), package.display_name().to_json());
+    object.insert("revision".to_string(), package.revision().to_json());
+    object.insert("license".to_string(), package.uses_license().map(|s| s.to_string()).to_json());
+    object.insert("description".to_string(), package.description().map(|s| s.to_string()).to_json());
+    object.insert("archives".to_string(), package.archives().to_json());
+    match id {
+        PackageId::Platform(_) => {
+            object.insert("api_level".to_string(), package.api_level().to_json());
+            object.insert("version".to_string(), package.version().map(|v| v.to_string()).to_json());
+            object.insert("codename".to_string(), package.codename().map(|c| c.to_string()).to_json());
+            object.insert("min_tools_rev".to_string(), min_revision(package, PackageId::Tools).to_json());
+        }
+        PackageId::Source(_) => {
+            object.insert("api_level".to_string(), package.api_level().to_json());
+        }
+        PackageId::Tools => {
+            object.insert("min_platform_tools_rev".to_string(), min_revision(package, PackageId::PlatformTools).to_json());
+        }
+        _ => {}
+    }
+    return object;
+}
+
+impl ToJson for InstalledPackage {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("id".to_string(), self.id.to_string().to_json());
+        object.insert("path".to_string(), self.path.to_string_lossy().into_owned().to_json());
+        object.insert("revision".to_string(), self.revision.to_json());
+        return Json::Object(object);
+    }
+}
+
+impl<'a> ToJson for Step<'a> {
+    fn to_json(&self) -> Json {
+        let mut object = BTreeMap::new();
+        object.insert("id".to_string(), self.package.id().to_string().to_json());
+        object.insert("action".to_string(), self.action.as_str().to_json());
+        object.insert("revision".to_string(), self.package.revision().to_json());
+        object.insert("installed_revision".to_string(), self.installed_revision.to_json());
+        object.insert("required_by".to_string(), self.required_by.iter().map(|id| id.to_string()).collect::<Vec<String>>().to_json());
+        object.insert("archive".to_string(), self.package.archive_for_host().map(|a| a.to_json()).unwrap_or(Json::Null));
+        return Json::Object(object);
+    }
+}
+
+pub fn plan(plan: &Plan) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("steps".to_string(), plan.steps.to_json());
+    return Json::Object(object);
+}
+
+pub fn packages(sdk_repository: &SdkRepository) -> Json {
+    let packages: Vec<Json> = sdk_repository.packages().map(|p| Json::Object(package(p))).collect();
+    let mut object = BTreeMap::new();
+    object.insert("packages".to_string(), Json::Array(packages));
+    return Json::Object(object);
+}
+
+pub fn installed_packages(installed_packages: &Vec<InstalledPackage>) -> Json {
+    let mut object = BTreeMap::new();
+    object.insert("packages".to_string(), installed_packages.to_json());
+    return Json::Object(object);
+}
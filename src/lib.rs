@@ -1,10 +1,39 @@
 extern crate xml;
 extern crate hyper;
-extern crate tempfile;
+extern crate hyper_native_tls;
+extern crate native_tls;
 extern crate crypto;
+extern crate rustc_serialize;
+extern crate toml;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate windows_sys;
 
 pub mod xmlhelper;
+pub mod fshelper;
 pub mod repository11;
+pub mod package_id;
+pub mod package;
+pub mod checksum;
+pub mod cache;
 pub mod downloader;
+pub mod error;
+pub mod fetcher;
+pub mod http;
+pub mod progress;
+pub mod json;
+pub mod local;
+pub mod lock;
+pub mod search;
+pub mod resolver;
+pub mod retry;
+pub mod transport;
+pub mod installer;
+pub mod manifest;
+pub mod lockfile;
 
 pub use downloader::Downloader;
+pub use installer::Installer;
+pub use package::Package;
+pub use package_id::PackageId;
@@ -0,0 +1,210 @@
+use package::Dependency;
+use package_id::PackageId;
+use repository11::Revision;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std;
+
+pub static SOURCE_PROPERTIES: &'static str = "source.properties";
+/// Prefix of the directory in the SDK root a package is moved to while it is removed, followed
+/// by the package id like the installer's staging and backup directories.
+pub static REMOVING_PREFIX: &'static str = ".removing-";
+
+#[derive(Debug)]
+pub struct LocalSdk {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub id: PackageId,
+    pub path: PathBuf,
+    pub revision: Option<Revision>,
+    pub properties: HashMap<String, String>,
+}
+
+impl LocalSdk {
+    pub fn new<P: AsRef<Path>>(root: P) -> LocalSdk {
+        LocalSdk {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        return &self.root;
+    }
+
+    pub fn package_path(&self, id: &PackageId) -> PathBuf {
+        return self.root.join(id.relative_path());
+    }
+
+    pub fn installed(&self, id: &PackageId) -> Option<InstalledPackage> {
+        let path = self.package_path(id);
+        match read_properties(&path.join(SOURCE_PROPERTIES)) {
+            Ok(properties) => Some(installed_package(id, path, properties)),
+            Err(_) => None,
+        }
+    }
+
+    /// Looks `id` up at its install path, then among the other installed packages by their
+    /// `source.properties`, e.g. in `platforms/android-6.0` installed by older SDK tools.
+    pub fn find(&self, id: &PackageId) -> Option<InstalledPackage> {
+        if let Some(package) = self.installed(id) {
+            return Some(package);
+        }
+        return self.installed_packages().into_iter().find(|p| p.id == *id);
+    }
+
+    /// Every directory holding `id`: its install path, and others whose `source.properties`
+    /// identifies them as `id`.
+    pub fn providers(&self, id: &PackageId) -> Vec<InstalledPackage> {
+        return self.installed_packages().into_iter().filter(|p| p.id == *id).collect();
+    }
+
+    /// Installed packages of any kind which depend on `id`.
+    pub fn dependents(&self, id: &PackageId) -> Vec<InstalledPackage> {
+        return self.installed_packages().into_iter().filter(|p| p.dependencies().iter().any(|d| d.id == *id)).collect();
+    }
+
+    /// Explains why removing `packages` together would break other installed packages: one
+    /// message for each package which another installed package depends on, unless that
+    /// package is removed too or another directory provides the same package.
+    pub fn removal_conflicts(&self, packages: &[InstalledPackage]) -> Vec<String> {
+        let removed = |package: &InstalledPackage| packages.iter().any(|p| p.path == package.path);
+        let mut conflicts = Vec::new();
+        let mut checked: Vec<&PackageId> = Vec::new();
+        for package in packages {
+            if checked.contains(&&package.id) {
+                continue;
+            }
+            checked.push(&package.id);
+            if self.providers(&package.id).iter().any(|p| !removed(p)) {
+                continue;
+            }
+            let dependents: Vec<String> = self.dependents(&package.id).iter().filter(|p| !removed(p)).map(|p| p.id.to_string()).collect();
+            if !dependents.is_empty() {
+                conflicts.push(format!("{} is required by {}", package.id, dependents.join(", ")));
+            }
+        }
+        return conflicts;
+    }
+
+    /// Every directory holding a `source.properties` at the depth packages of its kind are
+    /// installed at, identified by its path or else by its properties.
+    pub fn installed_packages(&self) -> Vec<InstalledPackage> {
+        let mut packages = Vec::new();
+        for &(kind, depth) in &[("ndk-bundle", 0), ("platform-tools", 0), ("tools", 0), ("platforms", 1), ("sources", 1), ("build-tools", 1), ("add-ons", 1), ("system-images", 3)] {
+            for path in dirs_at(&self.root.join(kind), depth) {
+                let properties = match read_properties(&path.join(SOURCE_PROPERTIES)) {
+                    Ok(properties) => properties,
+                    Err(_) => { continue; }
+                };
+                let id = path.strip_prefix(&self.root).ok().and_then(id_from_path).or_else(|| identify(kind, &properties));
+                if let Some(id) = id {
+                    packages.push(installed_package(&id, path, properties));
+                }
+            }
+        }
+        return packages;
+    }
+
+    /// Removes an installed package. Its directory is first renamed out of place, so that an
+    /// interrupted removal does not leave a partial package behind.
+    pub fn remove(&self, package: &InstalledPackage) -> std::io::Result<()> {
+        let removing_dir = self.root.join(format!("{}{}", REMOVING_PREFIX, package.id));
+        if removing_dir.exists() {
+            std::fs::remove_dir_all(&removing_dir)?;
+        }
+        std::fs::rename(&package.path, &removing_dir)?;
+        return std::fs::remove_dir_all(&removing_dir);
+    }
+
+}
+
+impl InstalledPackage {
+    /// Packages this one depends on, from the minimum revisions in its `source.properties`
+    /// and the kind of package.
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        let mut dependencies = self.id.implied_dependencies();
+        for &(key, ref id) in &[("Platform.MinToolsRev", PackageId::Tools), ("Platform.MinPlatformToolsRev", PackageId::PlatformTools)] {
+            if let Some(revision) = self.properties.get(key).and_then(|r| r.parse().ok()) {
+                dependencies.push(Dependency { id: id.clone(), min_revision: Some(revision) });
+            }
+        }
+        return dependencies;
+    }
+}
+
+fn installed_package(id: &PackageId, path: PathBuf, properties: HashMap<String, String>) -> InstalledPackage {
+    InstalledPackage {
+        id: id.clone(),
+        path: path,
+        revision: properties.get("Pkg.Revision").and_then(|r| r.parse().ok()),
+        properties: properties,
+    }
+}
+
+fn dirs_at(dir: &Path, depth: u32) -> Vec<PathBuf> {
+    if depth == 0 {
+        return if dir.is_dir() { vec![dir.to_path_buf()] } else { Vec::new() };
+    }
+    let mut dirs: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+        Err(_) => Vec::new(),
+    };
+    dirs.sort();
+    return dirs.iter().flat_map(|d| dirs_at(d, depth - 1)).collect();
+}
+
+fn id_from_path(relative_path: &Path) -> Option<PackageId> {
+    let components: Option<Vec<&str>> = relative_path.components().map(|c| c.as_os_str().to_str()).collect();
+    return components.and_then(|c| c.join(";").parse().ok());
+}
+
+/// Works out the package a directory of `kind` (e.g. `platforms`) holds from its properties:
+/// `Pkg.Path` where present, otherwise the API level or revision.
+fn identify(kind: &str, properties: &HashMap<String, String>) -> Option<PackageId> {
+    if let Some(id) = properties.get("Pkg.Path").and_then(|p| p.parse().ok()) {
+        return Some(id);
+    }
+    let api_level = properties.get("AndroidVersion.ApiLevel").and_then(|l| l.parse().ok());
+    match kind {
+        "platforms" => api_level.map(PackageId::Platform),
+        "sources" => api_level.map(PackageId::Source),
+        "build-tools" => properties.get("Pkg.Revision").and_then(|r| r.parse().ok()).map(PackageId::build_tools),
+        _ => None,
+    }
+}
+
+pub fn read_properties(path: &Path) -> std::io::Result<HashMap<String, String>> {
+    let file = File::open(path)?;
+    let mut properties = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        if let Some(i) = line.find(['=', ':']) {
+            properties.insert(unescape(line[..i].trim()), unescape(line[i + 1..].trim()));
+        }
+    }
+    return Ok(properties);
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                result.push(escaped);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    return result;
+}
@@ -0,0 +1,145 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use std;
+
+pub static LOCK_FILE: &'static str = ".android-sdk-cli.lock";
+
+/// Advisory lock on an SDK root, held while packages are installed or removed so that
+/// concurrent processes do not modify the SDK at the same time. It is released when dropped,
+/// or by the system when the process exits.
+#[derive(Debug)]
+pub struct SdkLock {
+    _lock: FileLock,
+    path: PathBuf,
+}
+
+/// Exclusive advisory lock on a file, released when dropped. The holder writes its process ID
+/// and command line into the file, to tell processes waiting for the lock who they wait for.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl SdkLock {
+    /// Locks `root`, waiting up to `timeout` for the process holding the lock to release it.
+    /// `waiting` is called with a description of that process before waiting.
+    pub fn acquire<F: FnOnce(&str)>(root: &Path, timeout: Duration, waiting: F) -> Result<SdkLock, String> {
+        std::fs::create_dir_all(root).map_err(|e| format!("failed to create {}: {}", root.display(), e))?;
+        let path = root.join(LOCK_FILE);
+        let lock = FileLock::acquire(&path, timeout, waiting)?;
+        return Ok(SdkLock {
+            _lock: lock,
+            path: path,
+        });
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+}
+
+impl FileLock {
+    /// Locks `path`, creating it if needed, waiting up to `timeout` for the process or thread
+    /// holding the lock to release it. `waiting` is called with a description of that process
+    /// before waiting.
+    pub fn acquire<F: FnOnce(&str)>(path: &Path, timeout: Duration, waiting: F) -> Result<FileLock, String> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        let started = Instant::now();
+        let mut waiting = Some(waiting);
+        while !try_lock(&file).map_err(|e| format!("failed to lock {}: {}", path.display(), e))? {
+            let holder = holder(&mut file);
+            if started.elapsed() >= timeout {
+                return Err(format!("timed out waiting for the lock on {} held by {}", path.display(), holder));
+            }
+            if let Some(waiting) = waiting.take() {
+                waiting(&holder);
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        let command_line: Vec<String> = std::env::args().collect();
+        file.set_len(0).and_then(|_| file.seek(SeekFrom::Start(0))).and_then(|_| write!(file, "{}\n{}\n", std::process::id(), command_line.join(" "))).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        return Ok(FileLock { file: file });
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Describes the process holding the lock from the process ID and command line it wrote into
+/// the lock file.
+fn holder(file: &mut File) -> String {
+    let mut content = String::new();
+    if file.seek(SeekFrom::Start(0)).and_then(|_| file.read_to_string(&mut content)).is_err() {
+        return "another process".to_string();
+    }
+    let mut lines = content.lines();
+    match (lines.next(), lines.next()) {
+        (Some(pid), Some(command_line)) if !pid.is_empty() => format!("process {} ({})", pid, command_line),
+        (Some(pid), _) if !pid.is_empty() => format!("process {}", pid),
+        _ => "another process".to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let e = std::io::Error::last_os_error();
+    match e.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted => Ok(false),
+        _ => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::{ERROR_LOCK_VIOLATION, HANDLE};
+    use windows_sys::Win32::Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY};
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+    // Locks the whole file, however long it gets. The lock is released when the handle is closed.
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    if unsafe { LockFileEx(file.as_raw_handle() as HANDLE, LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY, 0, u32::MAX, u32::MAX, &mut overlapped) } != 0 {
+        return Ok(true);
+    }
+    let e = std::io::Error::last_os_error();
+    if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        return Ok(false);
+    }
+    return Err(e);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SdkLock;
+    use std::time::Duration;
+    use std;
+
+    #[test]
+    fn report_holder_on_timeout() {
+        let root = std::env::temp_dir().join(format!("android-sdk-cli-lock-{}", std::process::id()));
+        let lock = SdkLock::acquire(&root, Duration::from_secs(0), |_| {}).unwrap();
+        let holder = format!("process {} (", std::process::id());
+
+        let mut waited_for = None;
+        let error = SdkLock::acquire(&root, Duration::from_millis(300), |h| waited_for = Some(h.to_string())).unwrap_err();
+        assert!(waited_for.unwrap().starts_with(&holder));
+        assert!(error.contains(&holder), "{}", error);
+        let error = SdkLock::acquire(&root, Duration::from_secs(0), |_| {}).unwrap_err();
+        assert!(error.starts_with(&format!("timed out waiting for the lock on {} held by {}", lock.path().display(), holder)), "{}", error);
+
+        drop(lock);
+        assert!(SdkLock::acquire(&root, Duration::from_secs(0), |_| {}).is_ok());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
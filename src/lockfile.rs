@@ -0,0 +1,304 @@
+use checksum::{Algorithm, Checksum};
+use fshelper;
+use local::LocalSdk;
+use package::Package;
+use package_id::PackageId;
+use repository11::{Archive, Revision, SdkRepository};
+use resolver::{Action, Plan, Step};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use toml;
+
+/// Exact revisions and archives of every package resolved from a manifest, so that later
+/// installations download byte-identical archives:
+///
+/// ```toml
+/// manifest-sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+///
+/// [[package]]
+/// id = "platforms;android-23"
+/// revision = "3"
+///
+/// [[package.archive]]
+/// sha1 = "f4f1e0ee23ba5fd2eb7ba7e3e8f3eb3b1ce37d6f"
+/// size = 70433421
+/// url = "android-23_r03.zip"
+/// ```
+///
+/// Packages are listed in installation order, dependencies first. Archives have one key per
+/// checksum of the manifest, named after its algorithm (`sha1` or `sha256`). URLs of archives
+/// under the repository are relative to it, so that the lockfile can be installed from another
+/// repository URL or a mirror. `manifest-sha256` is the digest of the manifest the packages
+/// were resolved from, which tells whether the lockfile is up to date with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub manifest_digest: String,
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub id: PackageId,
+    pub revision: Revision,
+    /// Archives for every host, with checksums sorted by algorithm.
+    pub archives: Vec<Archive>,
+}
+
+impl Package for LockedPackage {
+    fn id(&self) -> PackageId {
+        return self.id.clone();
+    }
+
+    fn display_name(&self) -> String {
+        return self.id.to_string();
+    }
+
+    fn revision(&self) -> Revision {
+        return self.revision.clone();
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return None;
+    }
+
+    fn description(&self) -> Option<&str> {
+        return None;
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+}
+
+fn lock_archive(archive: &Archive, base_url: &str) -> Archive {
+    let mut archive = archive.clone();
+    let base_url = format!("{}/", base_url.trim_end_matches('/'));
+    if archive.url.starts_with(&base_url) {
+        archive.url = archive.url[base_url.len()..].to_string();
+    }
+    archive.checksums.sort();
+    return archive;
+}
+
+impl Lockfile {
+    /// Locks every package of `plan`, resolved from the manifest with digest `manifest_digest`
+    /// and the repository at `base_url`. Packages kept at their installed revision are locked
+    /// at it when the repository still lists it, and otherwise at the planned revision, so that
+    /// every locked package can be installed from the repository.
+    pub fn from_plan(plan: &Plan, sdk_repository: &SdkRepository, base_url: &str, manifest_digest: &str) -> Lockfile {
+        let packages = plan.steps.iter().map(|step| {
+            let id = step.package.id();
+            let candidates = sdk_repository.find_all(&id);
+            let kept = match (step.action, step.installed_revision.as_ref()) {
+                (Action::Keep, Some(installed)) => candidates.iter().find(|p| p.revision() == *installed),
+                _ => None,
+            };
+            let package = kept.cloned().unwrap_or(step.package);
+            LockedPackage {
+                id: id,
+                revision: package.revision(),
+                archives: package.archives().iter().map(|a| lock_archive(a, base_url)).collect(),
+            }
+        }).collect();
+        return Lockfile {
+            manifest_digest: manifest_digest.to_string(),
+            packages: packages,
+        };
+    }
+
+    /// Plans installing exactly the locked revisions and archives.
+    pub fn plan<'a>(&'a self, local_sdk: &LocalSdk) -> Result<Plan<'a>, String> {
+        let mut steps = Vec::new();
+        for package in &self.packages {
+            let installed_revision = local_sdk.installed(&package.id).and_then(|p| p.revision);
+            let action = match installed_revision {
+                None => Action::Install,
+                Some(ref installed) if *installed < package.revision => Action::Upgrade,
+                Some(ref installed) if *installed > package.revision => Action::Downgrade,
+                Some(_) => Action::Keep,
+            };
+            if action != Action::Keep && package.archive_for_host().is_none() {
+                return Err(format!("{} {} has no archive for this host", package.id, package.revision));
+            }
+            steps.push(Step {
+                package: package,
+                action: action,
+                installed_revision: installed_revision,
+                required_by: Vec::new(),
+            });
+        }
+        return Ok(Plan { steps: steps });
+    }
+
+    pub fn load(path: &Path) -> Result<Lockfile, String> {
+        let mut content = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut content)).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        return content.parse().map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        return fshelper::write_atomically(path, self.to_string().as_bytes()).map_err(|e| format!("failed to write {}: {}", path.display(), e));
+    }
+
+    /// Describes how the repository at `base_url` no longer matches this lockfile: locked
+    /// revisions it does not list any more, or lists with other archives.
+    pub fn verify(&self, sdk_repository: &SdkRepository, base_url: &str) -> Vec<String> {
+        let mut differences = Vec::new();
+        for locked in &self.packages {
+            match sdk_repository.find_all(&locked.id).into_iter().find(|p| p.revision() == locked.revision) {
+                None => { differences.push(format!("{} {} is no longer in the repository", locked.id, locked.revision)); }
+                Some(package) => {
+                    let archives: Vec<Archive> = package.archives().iter().map(|a| lock_archive(a, base_url)).collect();
+                    if archives != locked.archives {
+                        differences.push(format!("{} {} has different archives than locked", locked.id, locked.revision));
+                    }
+                }
+            }
+        }
+        return differences;
+    }
+
+    /// Describes how `other` differs from this lockfile, one line per package.
+    pub fn diff(&self, other: &Lockfile) -> Vec<String> {
+        let mut differences = Vec::new();
+        for locked in &self.packages {
+            match other.packages.iter().find(|p| p.id == locked.id) {
+                None => { differences.push(format!("{} is locked but no longer required", locked.id)); }
+                Some(package) => {
+                    if package.revision != locked.revision {
+                        differences.push(format!("{} is locked at revision {} but resolves to {}", locked.id, locked.revision, package.revision));
+                    } else if package.archives != locked.archives {
+                        differences.push(format!("{} {} has different archives than locked", locked.id, locked.revision));
+                    }
+                }
+            }
+        }
+        for package in &other.packages {
+            if !self.packages.iter().any(|p| p.id == package.id) {
+                differences.push(format!("{} {} is not locked", package.id, package.revision));
+            }
+        }
+        return differences;
+    }
+}
+
+fn get_str<'a>(table: &'a toml::Table, key: &str) -> Result<&'a str, String> {
+    return table.get(key).and_then(|v| v.as_str()).ok_or(format!("missing string value: {}", key));
+}
+
+fn parse_archive(table: &toml::Table) -> Result<Archive, String> {
+    let host_os = match table.get("host-os").and_then(|v| v.as_str()) {
+        Some(s) => Some(s.parse()?),
+        None => None,
+    };
+    let host_bits = match table.get("host-bits").and_then(|v| v.as_str()) {
+        Some(s) => Some(s.parse()?),
+        None => None,
+    };
+    let size = match table.get("size").and_then(|v| v.as_integer()) {
+        Some(i) if i >= 0 => i as u64,
+        _ => { return Err("missing integer value: size".to_string()); }
+    };
+    let mut checksums = Vec::new();
+    for &algorithm in Algorithm::all() {
+        if table.contains_key(algorithm.as_str()) {
+            checksums.push(Checksum::new(algorithm, get_str(table, algorithm.as_str())?));
+        }
+    }
+    if checksums.is_empty() {
+        return Err("missing checksum: sha1 or sha256".to_string());
+    }
+    return Ok(Archive {
+        size: size,
+        checksums: checksums,
+        url: get_str(table, "url")?.to_string(),
+        host_os: host_os,
+        host_bits: host_bits,
+    });
+}
+
+fn parse_package(table: &toml::Table) -> Result<LockedPackage, String> {
+    let mut archives = Vec::new();
+    if let Some(values) = table.get("archive").and_then(|v| v.as_slice()) {
+        for value in values {
+            match value.as_table() {
+                Some(t) => archives.push(parse_archive(t)?),
+                None => { return Err("archive must be a table".to_string()); }
+            }
+        }
+    }
+    return Ok(LockedPackage {
+        id: get_str(table, "id")?.parse()?,
+        revision: get_str(table, "revision")?.parse()?,
+        archives: archives,
+    });
+}
+
+impl FromStr for Lockfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Lockfile, String> {
+        let mut parser = toml::Parser::new(s);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let messages: Vec<String> = parser.errors.iter().map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{}:{}: {}", line + 1, col + 1, e.desc)
+                }).collect();
+                return Err(format!("invalid lockfile:\n{}", messages.join("\n")));
+            }
+        };
+
+        let mut packages = Vec::new();
+        if let Some(values) = table.get("package").and_then(|v| v.as_slice()) {
+            for value in values {
+                match value.as_table() {
+                    Some(t) => packages.push(parse_package(t)?),
+                    None => { return Err("package must be a table".to_string()); }
+                }
+            }
+        }
+        return Ok(Lockfile {
+            manifest_digest: table.get("manifest-sha256").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            packages: packages,
+        });
+    }
+}
+
+impl fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut packages = Vec::new();
+        for package in &self.packages {
+            let mut archives = Vec::new();
+            for archive in &package.archives {
+                let mut t = BTreeMap::new();
+                if let Some(host_os) = archive.host_os {
+                    t.insert("host-os".to_string(), toml::Value::String(host_os.as_str().to_string()));
+                }
+                if let Some(host_bits) = archive.host_bits {
+                    t.insert("host-bits".to_string(), toml::Value::String(host_bits.as_str().to_string()));
+                }
+                t.insert("url".to_string(), toml::Value::String(archive.url.clone()));
+                t.insert("size".to_string(), toml::Value::Integer(archive.size as i64));
+                for checksum in &archive.checksums {
+                    t.insert(checksum.algorithm.as_str().to_string(), toml::Value::String(checksum.digest.clone()));
+                }
+                archives.push(toml::Value::Table(t));
+            }
+            let mut t = BTreeMap::new();
+            t.insert("id".to_string(), toml::Value::String(package.id.to_string()));
+            t.insert("revision".to_string(), toml::Value::String(package.revision.to_string()));
+            t.insert("archive".to_string(), toml::Value::Array(archives));
+            packages.push(toml::Value::Table(t));
+        }
+        let mut root = BTreeMap::new();
+        root.insert("manifest-sha256".to_string(), toml::Value::String(self.manifest_digest.clone()));
+        root.insert("package".to_string(), toml::Value::Array(packages));
+        return write!(f, "# This file is generated by android-sdk-cli. Do not edit it by hand.\n\n{}", toml::Value::Table(root));
+    }
+}
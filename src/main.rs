@@ -1,35 +1,694 @@
 extern crate android_sdk_cli;
-extern crate hyper;
+extern crate getopts;
+extern crate rustc_serialize;
 
-use std::path::Path;
-use android_sdk_cli::xmlhelper;
+use android_sdk_cli::cache::{Cache, CacheEntry, EntryKind};
+use android_sdk_cli::downloader::Mirrors;
+use android_sdk_cli::fetcher::{self, Fetcher};
+use android_sdk_cli::http;
+use android_sdk_cli::json;
+use android_sdk_cli::installer;
+use android_sdk_cli::local::{InstalledPackage, LocalSdk};
+use android_sdk_cli::lock::SdkLock;
+use android_sdk_cli::lockfile::Lockfile;
+use android_sdk_cli::manifest;
+use android_sdk_cli::PackageId;
+use android_sdk_cli::progress::ProgressReporter;
 use android_sdk_cli::repository11;
+use android_sdk_cli::resolver::{self, Request};
+use android_sdk_cli::search;
+use android_sdk_cli::transport::{SchemeTransport, Transport};
+use rustc_serialize::json::{Json, ToJson};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-fn main() {
-    let client = hyper::Client::new();
-    let response = client.get(repository11::XML_URL).send().unwrap();
-    let stream = xmlhelper::parse(response).unwrap();
-    let sdk_repository = repository11::parse_sdk_repository(&mut stream.into_iter()).unwrap();
+static DEFAULT_JOBS: usize = 4;
+static DEFAULT_LOCK_TIMEOUT_SECS: u64 = 10 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat { Text, Json }
 
-    for ndk in sdk_repository.ndks {
-        println!("{:?}", ndk);
+fn parse_format(matches: &getopts::Matches) -> Result<OutputFormat, String> {
+    match matches.opt_str("format") {
+        None => Ok(OutputFormat::Text),
+        Some(ref f) if f == "text" => Ok(OutputFormat::Text),
+        Some(ref f) if f == "json" => Ok(OutputFormat::Json),
+        Some(f) => Err(format!("unknown output format: {}", f)),
     }
-    for platform in sdk_repository.platforms {
-        println!("{:?}", platform);
+}
+
+fn parse_sdk_root(matches: &getopts::Matches) -> Result<PathBuf, String> {
+    if let Some(root) = matches.opt_str("sdk-root") {
+        return Ok(PathBuf::from(root));
+    }
+    match std::env::var("ANDROID_HOME") {
+        Ok(root) => Ok(PathBuf::from(root)),
+        Err(_) => Err("SDK root is not specified: use --sdk-root or set ANDROID_HOME".to_string()),
+    }
+}
+
+fn repository_opts(opts: &mut getopts::Options) {
+    opts.optflag("", "offline", "use the cached repository manifest without accessing the network");
+    opts.optopt("", "max-age", "revalidate the cached repository manifest once older than DURATION (default: 1h)", "DURATION");
+    opts.optopt("", "repo-url", "base URL of the repository or URL of its repository-11.xml (default: $ANDROID_SDK_REPO_URL or https://dl.google.com/android/repository)", "URL");
+    opts.optmulti("", "mirror", "base URL of a mirror to fetch the manifest and archives from when the repository fails (default: $ANDROID_SDK_REPO_MIRRORS)", "URL");
+    opts.optopt("", "proxy", "HTTP proxy for all requests (default: $http_proxy and $https_proxy)", "URL");
+    opts.optmulti("", "ca-cert", "trust the certificates in FILE in addition to the system ones", "FILE");
+}
+
+fn transport(matches: &getopts::Matches) -> Result<Arc<dyn Transport>, String> {
+    let mut config = http::Config::from_env()?;
+    if let Some(proxy) = matches.opt_str("proxy") {
+        let proxy: http::Proxy = proxy.parse()?;
+        config.http_proxy = Some(proxy.clone());
+        config.https_proxy = Some(proxy);
     }
-    for source in sdk_repository.sources {
-        println!("{:?}", source);
+    config.ca_certificates = matches.opt_strs("ca-cert").into_iter().map(PathBuf::from).collect();
+    return Ok(Arc::new(SchemeTransport::new(http::Client::new(&config)?)));
+}
+
+/// The URL of the manifest, and the base URL of the repository.
+fn repository_url(matches: &getopts::Matches) -> (String, String) {
+    let url = matches.opt_str("repo-url").or(std::env::var("ANDROID_SDK_REPO_URL").ok()).unwrap_or(repository11::XML_URL_BASE.to_string());
+    if url.ends_with(".xml") {
+        let base_url = url[..url.rfind('/').unwrap_or(0)].to_string();
+        return (url, base_url);
     }
-    for build_tool in sdk_repository.build_tools {
-        println!("{:?}", build_tool);
+    let base_url = url.trim_end_matches('/').to_string();
+    return (format!("{}/{}", base_url, repository11::XML_FILE), base_url);
+}
+
+fn mirrors(matches: &getopts::Matches) -> Mirrors {
+    let urls = if matches.opt_present("mirror") {
+        matches.opt_strs("mirror")
+    } else {
+        std::env::var("ANDROID_SDK_REPO_MIRRORS").map(|s| s.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect()).unwrap_or_default()
+    };
+    return Mirrors {
+        base_url: repository_url(matches).1,
+        urls: urls,
+    };
+}
+
+fn fetch_repository(matches: &getopts::Matches, transport: &dyn Transport) -> Result<repository11::SdkRepository, String> {
+    let mut fetcher = Fetcher::new(transport, Cache::default_dir().map(Cache::new)).offline(matches.opt_present("offline")).mirrors(mirrors(matches));
+    if let Some(max_age) = matches.opt_str("max-age") {
+        fetcher = fetcher.max_age(parse_duration(&max_age)?);
     }
-    for platform_tool in &sdk_repository.platform_tools {
-        println!("{:?}", platform_tool);
+    return fetcher.fetch(&repository_url(matches).0);
+}
+
+fn cache_dir() -> Result<PathBuf, String> {
+    return Cache::default_dir().ok_or("cache directory is not available: set ANDROID_SDK_CLI_CACHE_DIR".to_string());
+}
+
+fn parse_jobs(matches: &getopts::Matches) -> Result<usize, String> {
+    match matches.opt_str("jobs") {
+        Some(s) => match s.parse() {
+            Ok(0) | Err(_) => Err(format!("invalid number of jobs: {}", s)),
+            Ok(jobs) => Ok(jobs),
+        },
+        None => Ok(DEFAULT_JOBS),
     }
+}
 
-    let downloader = android_sdk_cli::Downloader::new(client);
+fn create_downloader(matches: &getopts::Matches, transport: Arc<dyn Transport>, reporter: &Arc<ProgressReporter>) -> Result<android_sdk_cli::Downloader, String> {
+    let downloader = android_sdk_cli::Downloader::with_transport(transport, Cache::default_dir().map(Cache::new));
+    return Ok(downloader.observer(reporter.clone()).mirrors(mirrors(matches)).lock_timeout(lock_timeout(matches)?));
+}
+
+fn print_usage(program: &str, opts: &getopts::Options) {
+    let brief = format!("Usage: {} [options] <command> [<args>]\n\nCommands:\n    list         List packages available in the repository\n    installed    List packages installed in the SDK root\n    info         Show details of a package\n    search       Search packages by id, API level, version, codename or description\n    plan         Show what installing packages would do\n    install      Install packages and their dependencies\n    uninstall    Remove installed packages\n    sync         Install packages listed in android-sdk.toml\n    cache        Manage downloaded archives (list, verify, clean)", program);
+    print!("{}", opts.usage(&brief));
+}
+
+fn list(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "format", "output format (text or json)", "FORMAT");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let format = parse_format(&matches)?;
 
-    // TODO: Install selected archive
-    let archive = &sdk_repository.platform_tools[0].archives[0];
-    downloader.download(&archive.absolute_url(), &archive.checksum, Path::new("/tmp/android-sdk-cli"));
+    let transport = transport(&matches)?;
+    let sdk_repository = fetch_repository(&matches, &*transport)?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", json::packages(&sdk_repository).pretty());
+        }
+        OutputFormat::Text => {
+            for package in sdk_repository.packages() {
+                println!("{}\t{}\t{}", package.id(), package.revision(), package.display_name());
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn installed(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    opts.optopt("", "format", "output format (text or json)", "FORMAT");
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let format = parse_format(&matches)?;
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+
+    let installed_packages = local_sdk.installed_packages();
+    match format {
+        OutputFormat::Json => {
+            println!("{}", json::installed_packages(&installed_packages).pretty());
+        }
+        OutputFormat::Text => {
+            for installed_package in &installed_packages {
+                println!("{}\t{}\t{}", installed_package.id, installed_package.revision.as_ref().map(|r| r.to_string()).unwrap_or("-".to_string()), installed_package.path.display());
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn license_excerpt(text: &str) -> String {
+    let lines: Vec<&str> = text.trim().lines().collect();
+    let mut excerpt = lines.iter().take(5).map(|line| line.trim()).collect::<Vec<&str>>().join("\n        ");
+    if lines.len() > 5 {
+        excerpt.push_str("\n        ...");
+    }
+    return excerpt;
+}
+
+fn info(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "format", "output format (text or json)", "FORMAT");
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let format = parse_format(&matches)?;
+    if matches.free.len() != 1 {
+        return Err("Usage: info <package-id>".to_string());
+    }
+    let id: PackageId = matches.free[0].parse()?;
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+
+    let transport = transport(&matches)?;
+    let sdk_repository = fetch_repository(&matches, &*transport)?;
+    let package = match sdk_repository.find(&id) {
+        Some(package) => package,
+        None => { return Err(format!("package not found: {}", id)); }
+    };
+    let license_text = package.uses_license().and_then(|l| sdk_repository.licenses.get(l));
+    let installed_package = local_sdk.installed(&id);
+
+    match format {
+        OutputFormat::Json => {
+            let mut object = json::package(package);
+            object.insert("license_text".to_string(), license_text.cloned().to_json());
+            object.insert("installed".to_string(), installed_package.to_json());
+            println!("{}", Json::Object(object).pretty());
+        }
+        OutputFormat::Text => {
+            println!("{} ({})", package.id(), package.display_name());
+            if let Some(description) = package.description() {
+                println!("    Description: {}", description);
+            }
+            println!("    Revision: {}", package.revision());
+            if let Some(api_level) = package.api_level() {
+                println!("    API level: {}", api_level);
+            }
+            if let Some(codename) = package.codename() {
+                println!("    Codename: {}", codename);
+            }
+            if let Some(license) = package.uses_license() {
+                println!("    License: {}", license);
+                if let Some(text) = license_text {
+                    println!("        {}", license_excerpt(text));
+                }
+            }
+            println!("    Archives:");
+            for archive in package.archives() {
+                let host = match (archive.host_os, archive.host_bits) {
+                    (Some(os), Some(bits)) => format!("{} {}-bit", os.as_str(), bits.as_str()),
+                    (Some(os), None) => os.as_str().to_string(),
+                    (None, Some(bits)) => format!("any {}-bit", bits.as_str()),
+                    (None, None) => "any".to_string(),
+                };
+                println!("        {}", archive.url);
+                println!("            Host: {}", host);
+                println!("            Size: {}", archive.size);
+                for checksum in &archive.checksums {
+                    println!("            {}: {}", checksum.algorithm.display_name(), checksum.digest);
+                }
+                println!("            URL: {}", archive.absolute_url());
+            }
+            match installed_package {
+                Some(installed_package) => {
+                    println!("    Installed: {} (revision {})", installed_package.path.display(), installed_package.revision.map(|r| r.to_string()).unwrap_or("unknown".to_string()));
+                }
+                None => {
+                    println!("    Installed: no");
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn search(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "format", "output format (text or json)", "FORMAT");
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let format = parse_format(&matches)?;
+    if matches.free.is_empty() {
+        return Err("Usage: search <term>".to_string());
+    }
+    let term = matches.free.join(" ");
+    // Searching works without a local SDK; installation status is shown only when one is given.
+    let local_sdk = parse_sdk_root(&matches).ok().map(LocalSdk::new);
+
+    let transport = transport(&matches)?;
+    let sdk_repository = fetch_repository(&matches, &*transport)?;
+    let results = search::search(&sdk_repository, &term);
+
+    match format {
+        OutputFormat::Json => {
+            let mut packages = Vec::new();
+            for result in &results {
+                if let Some(package) = sdk_repository.find(&result.id) {
+                    let mut object = json::package(package);
+                    object.insert("score".to_string(), result.score.to_json());
+                    object.insert("installed".to_string(), local_sdk.as_ref().and_then(|l| l.installed(&result.id)).to_json());
+                    packages.push(Json::Object(object));
+                }
+            }
+            let mut object = std::collections::BTreeMap::new();
+            object.insert("packages".to_string(), Json::Array(packages));
+            println!("{}", Json::Object(object).pretty());
+        }
+        OutputFormat::Text => {
+            for result in &results {
+                if let Some(package) = sdk_repository.find(&result.id) {
+                    let installed = match local_sdk.as_ref().and_then(|l| l.installed(&result.id)) {
+                        Some(_) => "installed",
+                        None => "-",
+                    };
+                    println!("{}\t{}\t{}\t{}", package.id(), package.revision(), installed, package.description().unwrap_or(""));
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn parse_package_ids(args: &[String]) -> Result<Vec<PackageId>, String> {
+    let mut ids = Vec::new();
+    for arg in args {
+        ids.push(arg.parse()?);
+    }
+    return Ok(ids);
+}
+
+fn print_plan(plan: &resolver::Plan) {
+    for step in &plan.steps {
+        let mut line = format!("{:<9} {} {}", step.action.as_str(), step.package.id(), step.package.revision());
+        if let Some(ref installed_revision) = step.installed_revision {
+            line.push_str(&format!(" (installed: {})", installed_revision));
+        }
+        if !step.required_by.is_empty() {
+            line.push_str(&format!(" (required by {})", step.required_by.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ")));
+        }
+        println!("{}", line);
+    }
+}
+
+fn plan(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "format", "output format (text or json)", "FORMAT");
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let format = parse_format(&matches)?;
+    if matches.free.is_empty() {
+        return Err("Usage: plan <package-id>...".to_string());
+    }
+    let requests: Vec<Request> = parse_package_ids(&matches.free)?.into_iter().map(Request::from).collect();
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+
+    let transport = transport(&matches)?;
+    let sdk_repository = fetch_repository(&matches, &*transport)?;
+    let plan = resolver::resolve(&sdk_repository, &local_sdk, &requests)?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", json::plan(&plan).pretty());
+        }
+        OutputFormat::Text => {
+            print_plan(&plan);
+        }
+    }
+    return Ok(());
+}
+
+fn install(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    opts.optopt("j", "jobs", "number of archives downloaded concurrently (default: 4)", "N");
+    opts.optopt("", "lock-timeout", "how long to wait for another process modifying the SDK or downloading the same archive (default: 10m)", "DURATION");
+    opts.optopt("", "from-dir", "install from archives and repository-11.xml in DIR without accessing the network", "DIR");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    if matches.free.is_empty() {
+        return Err("Usage: install <package-id>...".to_string());
+    }
+    let requests: Vec<Request> = parse_package_ids(&matches.free)?.into_iter().map(Request::from).collect();
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+    let jobs = parse_jobs(&matches)?;
+
+    let reporter = Arc::new(ProgressReporter::new());
+    let (sdk_repository, downloader) = match matches.opt_str("from-dir").map(PathBuf::from) {
+        Some(dir) => {
+            let sdk_repository = fetcher::load_repository(&dir.join(repository11::XML_FILE))?;
+            (sdk_repository, android_sdk_cli::Downloader::from_dir(dir))
+        }
+        None => {
+            let transport = transport(&matches)?;
+            let sdk_repository = fetch_repository(&matches, &*transport)?;
+            (sdk_repository, create_downloader(&matches, transport, &reporter)?)
+        }
+    };
+    let _lock = lock_sdk(&matches, &local_sdk)?;
+    let plan = resolver::resolve(&sdk_repository, &local_sdk, &requests)?;
+    print_plan(&plan);
+
+    let installer = android_sdk_cli::Installer::new(&downloader, &local_sdk);
+    let packages: Vec<&dyn android_sdk_cli::Package> = plan.pending().iter().map(|step| step.package).collect();
+    installer.install_all(&packages, jobs, |package| {
+        reporter.clear();
+        println!("Installed {} {}", package.id(), package.revision());
+    })?;
+    return Ok(());
+}
+
+fn uninstall(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    opts.optopt("", "lock-timeout", "how long to wait for another process modifying the SDK (default: 10m)", "DURATION");
+    opts.optflag("f", "force", "remove packages even if other installed packages depend on them");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    if matches.free.is_empty() {
+        return Err("Usage: uninstall [--force] <package-id>...".to_string());
+    }
+    let ids = parse_package_ids(&matches.free)?;
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+    let _lock = lock_sdk(&matches, &local_sdk)?;
+
+    let mut packages: Vec<InstalledPackage> = Vec::new();
+    for id in &ids {
+        match local_sdk.find(id) {
+            // A package named twice is removed once.
+            Some(ref package) if packages.iter().any(|p| p.path == package.path) => {}
+            Some(package) => packages.push(package),
+            None => { return Err(format!("{} is not installed", id)); }
+        }
+    }
+    let conflicts = local_sdk.removal_conflicts(&packages);
+    if !conflicts.is_empty() && !matches.opt_present("force") {
+        return Err(format!("{}\nuse --force to uninstall anyway", conflicts.join("\n")));
+    }
+    for package in packages {
+        local_sdk.remove(&package).map_err(|e| format!("failed to remove {}: {}", package.path.display(), e))?;
+        println!("Uninstalled {} {}", package.id, package.revision.map(|r| r.to_string()).unwrap_or("(unknown revision)".to_string()));
+    }
+    return Ok(());
+}
+
+fn sync(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    repository_opts(&mut opts);
+    opts.optopt("", "sdk-root", "path to the Android SDK (default: $ANDROID_HOME)", "DIR");
+    opts.optopt("j", "jobs", "number of archives downloaded concurrently (default: 4)", "N");
+    opts.optopt("", "lock-timeout", "how long to wait for another process modifying the SDK or downloading the same archive (default: 10m)", "DURATION");
+    opts.optopt("", "manifest", "path to the manifest (default: android-sdk.toml)", "FILE");
+    opts.optopt("", "lockfile", "path to the lockfile (default: the manifest path with .lock extension)", "FILE");
+    opts.optflag("", "locked", "fail if the lockfile is missing, out of date or no longer matches the repository, or with --update if it would change");
+    opts.optflag("", "update", "resolve the manifest again even if the lockfile is up to date");
+    opts.optflag("", "prune", "remove installed packages not listed in the manifest");
+    opts.optflag("n", "dry-run", "only show what would be done");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let manifest_path = PathBuf::from(matches.opt_str("manifest").unwrap_or(manifest::MANIFEST_FILE.to_string()));
+    let manifest = manifest::Manifest::load(&manifest_path)?;
+    let lockfile_path = matches.opt_str("lockfile").map(PathBuf::from).unwrap_or(manifest_path.with_extension("lock"));
+    let local_sdk = LocalSdk::new(parse_sdk_root(&matches)?);
+    let jobs = parse_jobs(&matches)?;
+
+    let locked = if lockfile_path.exists() { Some(Lockfile::load(&lockfile_path)?) } else { None };
+    let up_to_date = locked.as_ref().map(|l| l.manifest_digest == manifest.digest).unwrap_or(false);
+    if matches.opt_present("locked") && !up_to_date {
+        return Err(format!("{} is missing or out of date with {}", lockfile_path.display(), manifest_path.display()));
+    }
+
+    let transport = transport(&matches)?;
+    let _lock = if matches.opt_present("dry-run") { None } else { Some(lock_sdk(&matches, &local_sdk)?) };
+    // The manifest is only resolved against the repository again when it changed since the
+    // lockfile was written, or on request. Otherwise the locked archives are installed, once
+    // checked against the repository with --locked.
+    let lockfile = match locked {
+        Some(ref locked) if up_to_date && !matches.opt_present("update") => {
+            if matches.opt_present("locked") {
+                let sdk_repository = fetch_repository(&matches, &*transport)?;
+                let differences = locked.verify(&sdk_repository, &repository_url(&matches).1);
+                if !differences.is_empty() {
+                    return Err(format!("the repository no longer matches {}:\n{}", lockfile_path.display(), differences.join("\n")));
+                }
+            }
+            locked.clone()
+        }
+        _ => {
+            let sdk_repository = fetch_repository(&matches, &*transport)?;
+            let requests = manifest.requests(&sdk_repository, &local_sdk)?;
+            let plan = resolver::resolve(&sdk_repository, &local_sdk, &requests)?;
+            Lockfile::from_plan(&plan, &sdk_repository, &repository_url(&matches).1, &manifest.digest)
+        }
+    };
+    if matches.opt_present("locked") {
+        let differences = locked.as_ref().map(|l| l.diff(&lockfile)).unwrap_or_default();
+        if !differences.is_empty() {
+            return Err(format!("{} does not match the repository:\n{}", lockfile_path.display(), differences.join("\n")));
+        }
+    }
+    let plan = lockfile.plan(&local_sdk)?;
+    print_plan(&plan);
+
+    let mut extras = Vec::new();
+    if matches.opt_present("prune") {
+        let wanted: Vec<PackageId> = plan.steps.iter().map(|s| s.package.id()).collect();
+        for installed_package in local_sdk.installed_packages() {
+            if !wanted.contains(&installed_package.id) && !manifest.declares(&installed_package) {
+                println!("{:<9} {}", "remove", installed_package.id);
+                extras.push(installed_package);
+            }
+        }
+        let conflicts = local_sdk.removal_conflicts(&extras);
+        if !conflicts.is_empty() {
+            return Err(format!("cannot prune packages other installed packages depend on:\n{}", conflicts.join("\n")));
+        }
+    }
+    if matches.opt_present("dry-run") {
+        return Ok(());
+    }
+
+    let reporter = Arc::new(ProgressReporter::new());
+    let downloader = create_downloader(&matches, transport, &reporter)?;
+    let installer = android_sdk_cli::Installer::new(&downloader, &local_sdk).base_url(&repository_url(&matches).1);
+    let packages: Vec<&dyn android_sdk_cli::Package> = plan.pending().iter().map(|step| step.package).collect();
+    installer.install_all(&packages, jobs, |package| {
+        reporter.clear();
+        println!("Installed {} {}", package.id(), package.revision());
+    })?;
+    if locked.as_ref() != Some(&lockfile) {
+        lockfile.save(&lockfile_path)?;
+    }
+    for extra in extras {
+        println!("Removing {}", extra.id);
+        local_sdk.remove(&extra).map_err(|e| format!("failed to remove {}: {}", extra.path.display(), e))?;
+    }
+    return Ok(());
+}
+
+fn lock_timeout(matches: &getopts::Matches) -> Result<Duration, String> {
+    return match matches.opt_str("lock-timeout") {
+        Some(s) => parse_duration(&s),
+        None => Ok(Duration::from_secs(DEFAULT_LOCK_TIMEOUT_SECS)),
+    };
+}
+
+/// Locks the SDK root against other processes installing or removing packages, waiting for
+/// up to `--lock-timeout`.
+fn lock_sdk(matches: &getopts::Matches, local_sdk: &LocalSdk) -> Result<SdkLock, String> {
+    let lock = SdkLock::acquire(local_sdk.root(), lock_timeout(matches)?, |holder| {
+        println!("Waiting for {} to finish with {}", holder, local_sdk.root().display());
+    })?;
+    // Installations and removals interrupted before can only be cleaned up by the lock holder.
+    installer::recover(local_sdk)?;
+    return Ok(lock);
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    // The unit is the last character, which is not necessarily a single byte.
+    let (i, unit) = match s.char_indices().last() {
+        Some(last) => last,
+        None => { return Err("duration must not be empty".to_string()); }
+    };
+    let invalid = || format!("invalid duration (expected e.g. 30d, 12h, 10m or 60s): {}", s);
+    let seconds: u64 = match unit {
+        'd' => 24 * 60 * 60,
+        'h' => 60 * 60,
+        'm' => 60,
+        's' => 1,
+        _ => { return Err(invalid()); }
+    };
+    let n = s[..i].parse::<u64>().map_err(|_| invalid())?;
+    return n.checked_mul(seconds).map(Duration::from_secs).ok_or(format!("duration is too long: {}", s));
+}
+
+fn format_age(modified: SystemTime) -> String {
+    let seconds = modified.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+    if seconds >= 24 * 60 * 60 {
+        format!("{}d", seconds / (24 * 60 * 60))
+    } else if seconds >= 60 * 60 {
+        format!("{}h", seconds / (60 * 60))
+    } else {
+        format!("{}m", seconds / 60)
+    }
+}
+
+fn cache(args: &[String]) -> Result<(), String> {
+    let mut opts = getopts::Options::new();
+    opts.optopt("", "older-than", "clean: only remove archives downloaded or last used, and partial downloads last written, longer ago than DURATION (e.g. 30d)", "DURATION");
+    let matches = opts.parse(args).map_err(|e| e.to_string())?;
+    let cache = Cache::new(cache_dir()?);
+    let entries = cache.entries().map_err(|e| format!("failed to read {}: {}", cache.dir().display(), e))?;
+
+    match matches.free.first().map(|s| s.as_str()) {
+        Some("list") => {
+            let mut total = 0;
+            for entry in &entries {
+                match entry.kind {
+                    EntryKind::Archive => { println!("{}\t{}\t{}", entry.checksum.digest, entry.size, format_age(entry.modified)); }
+                    EntryKind::Partial => { println!("{}\t{}\t{}\tpartial", entry.checksum.digest, entry.size, format_age(entry.modified)); }
+                    EntryKind::Lock => {}
+                }
+                total += entry.size;
+            }
+            let archives = entries.iter().filter(|e| e.kind == EntryKind::Archive).count();
+            println!("{} archives, {} bytes in {}", archives, total, cache.dir().display());
+        }
+        Some("verify") => {
+            let mut corrupted = 0;
+            for entry in entries.iter().filter(|e| e.kind == EntryKind::Archive) {
+                if entry.verify().map_err(|e| format!("failed to read {}: {}", entry.path.display(), e))? {
+                    println!("{}\tok", entry.checksum.digest);
+                } else {
+                    println!("{}\tcorrupted, removed", entry.checksum.digest);
+                    entry.remove().map_err(|e| format!("failed to remove {}: {}", entry.path.display(), e))?;
+                    corrupted += 1;
+                }
+            }
+            if corrupted > 0 {
+                return Err(format!("{} corrupted archives were removed", corrupted));
+            }
+        }
+        Some("clean") => {
+            let older_than = match matches.opt_str("older-than") {
+                Some(s) => Some(parse_duration(&s)?),
+                None => None,
+            };
+            let expired = |entry: &CacheEntry| match older_than {
+                Some(duration) => entry.modified.elapsed().map(|age| age > duration).unwrap_or(false),
+                None => true,
+            };
+            for entry in &entries {
+                // A lock is kept as long as the partial download it guards.
+                let guarding = entry.kind == EntryKind::Lock && entries.iter().any(|e| e.kind == EntryKind::Partial && e.checksum == entry.checksum && !expired(e));
+                if expired(entry) && !guarding {
+                    match entry.kind {
+                        EntryKind::Archive => { println!("Removing {}", entry.checksum.digest); }
+                        EntryKind::Partial => { println!("Removing partial download of {}", entry.checksum.digest); }
+                        EntryKind::Lock => {}
+                    }
+                    entry.remove().map_err(|e| format!("failed to remove {}: {}", entry.path.display(), e))?;
+                }
+            }
+        }
+        _ => { return Err("Usage: cache list|verify|clean [--older-than DURATION]".to_string()); }
+    }
+    return Ok(());
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let program = args.first().cloned().filter(|p| !p.is_empty()).unwrap_or("android-sdk-cli".to_string());
+
+    let mut opts = getopts::Options::new();
+    opts.parsing_style(getopts::ParsingStyle::StopAtFirstFree);
+    opts.optflag("h", "help", "print this help menu");
+    let matches = match opts.parse(args.iter().skip(1)) {
+        Ok(m) => m,
+        Err(e) => {
+            let _ = writeln!(std::io::stderr(), "{}", e);
+            std::process::exit(1);
+        }
+    };
+    if matches.opt_present("help") || matches.free.is_empty() {
+        print_usage(&program, &opts);
+        return;
+    }
+
+    let command = matches.free[0].clone();
+    let command_args = &matches.free[1..];
+    let result = match command.as_str() {
+        "list" => list(command_args),
+        "installed" => installed(command_args),
+        "info" => info(command_args),
+        "search" => search(command_args),
+        "plan" => plan(command_args),
+        "install" => install(command_args),
+        "uninstall" => uninstall(command_args),
+        "sync" => sync(command_args),
+        "cache" => cache(command_args),
+        _ => Err(format!("unknown command: {}", command)),
+    };
+    if let Err(e) = result {
+        let _ = writeln!(std::io::stderr(), "{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_duration;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(Ok(Duration::from_secs(30 * 24 * 60 * 60)), parse_duration("30d"));
+        assert_eq!(Ok(Duration::from_secs(12 * 60 * 60)), parse_duration("12h"));
+        assert_eq!(Ok(Duration::from_secs(10 * 60)), parse_duration("10m"));
+        assert_eq!(Ok(Duration::from_secs(0)), parse_duration("0s"));
+    }
+
+    #[test]
+    fn reject_invalid_durations() {
+        for s in &["", "30", "d", "-1d", "1.5h", "30 d", "30D", "1dé", "é"] {
+            assert!(parse_duration(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn reject_overflowing_durations() {
+        assert_eq!(Ok(Duration::from_secs(u64::MAX)), parse_duration(&format!("{}s", u64::MAX)));
+        assert_eq!(Err(format!("duration is too long: {}d", u64::MAX / 60)), parse_duration(&format!("{}d", u64::MAX / 60)));
+        assert!(parse_duration(&format!("{}0s", u64::MAX)).is_err());
+    }
 }
@@ -0,0 +1,287 @@
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use local::{InstalledPackage, LocalSdk};
+use package::Package;
+use package_id::PackageId;
+use repository11::{Revision, SdkRepository};
+use resolver::Request;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use toml;
+
+pub static MANIFEST_FILE: &'static str = "android-sdk.toml";
+
+/// Constraint on a package revision: `*`, `>=23.0.1`, `23.0.*` or an exact `23.0.3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionReq {
+    Any,
+    AtLeast(Revision),
+    Prefix(Vec<u32>),
+    Exact(Revision),
+}
+
+impl VersionReq {
+    pub fn matches(&self, revision: &Revision) -> bool {
+        match *self {
+            VersionReq::Any => true,
+            VersionReq::AtLeast(ref min) => revision >= min,
+            VersionReq::Prefix(ref prefix) => {
+                let components = [revision.major, revision.minor.unwrap_or(0), revision.micro.unwrap_or(0)];
+                revision.preview.is_none() && prefix.len() <= components.len() && prefix.iter().zip(components.iter()).all(|(a, b)| a == b)
+            }
+            VersionReq::Exact(ref exact) => revision == exact,
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<VersionReq, String> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(VersionReq::Any);
+        }
+        if let Some(minimum) = s.strip_prefix(">=") {
+            return Ok(VersionReq::AtLeast(minimum.trim().parse()?));
+        }
+        if let Some(components) = s.strip_suffix(".*") {
+            let mut prefix = Vec::new();
+            for component in components.split('.') {
+                prefix.push(component.parse::<u32>().map_err(|_| format!("invalid version requirement: {}", s))?);
+            }
+            return Ok(VersionReq::Prefix(prefix));
+        }
+        return Ok(VersionReq::Exact(s.parse()?));
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VersionReq::Any => write!(f, "*"),
+            VersionReq::AtLeast(ref min) => write!(f, ">={}", min),
+            VersionReq::Prefix(ref prefix) => write!(f, "{}.*", prefix.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(".")),
+            VersionReq::Exact(ref exact) => write!(f, "{}", exact),
+        }
+    }
+}
+
+/// Packages an SDK is expected to contain, read from `android-sdk.toml`:
+///
+/// ```toml
+/// platform-tools = "*"
+/// tools = ">=25.1"
+/// build-tools = ["23.0.*", "24.0.2"]
+/// platforms = [23, 24]
+/// sources = [23]
+/// ndk-bundle = "*"
+/// ```
+///
+/// `platform-tools`, `tools` and `ndk-bundle` may also be `true`, the same as `"*"`.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    /// SHA-256 of the manifest text, recorded in the lockfile.
+    pub digest: String,
+    pub platform_tools: Option<VersionReq>,
+    pub tools: Option<VersionReq>,
+    pub ndk_bundle: Option<VersionReq>,
+    pub build_tools: Vec<VersionReq>,
+    pub platforms: Vec<u32>,
+    pub sources: Vec<u32>,
+}
+
+fn parse_version_reqs(key: &str, value: &toml::Value) -> Result<Vec<VersionReq>, String> {
+    match *value {
+        toml::Value::String(ref s) => Ok(vec![s.parse()?]),
+        toml::Value::Array(ref values) => {
+            let mut reqs = Vec::new();
+            for v in values {
+                match v.as_str() {
+                    Some(s) => reqs.push(s.parse()?),
+                    None => { return Err(format!("{} must be an array of version strings", key)); }
+                }
+            }
+            Ok(reqs)
+        }
+        _ => Err(format!("{} must be a version string or an array of them", key)),
+    }
+}
+
+/// `true` is accepted as a shorthand for `"*"`.
+fn parse_version_req(key: &str, value: &toml::Value) -> Result<VersionReq, String> {
+    match *value {
+        toml::Value::String(ref s) => s.parse(),
+        toml::Value::Boolean(true) => Ok(VersionReq::Any),
+        _ => Err(format!("{} must be a version string", key)),
+    }
+}
+
+fn parse_api_levels(key: &str, value: &toml::Value) -> Result<Vec<u32>, String> {
+    let values = match *value {
+        toml::Value::Array(ref values) => values.clone(),
+        ref v => vec![v.clone()],
+    };
+    let mut api_levels = Vec::new();
+    for v in values {
+        match v.as_integer() {
+            Some(i) if i > 0 && i <= i64::from(u32::MAX) => api_levels.push(i as u32),
+            Some(i) => { return Err(format!("invalid API level in {}: {}", key, i)); }
+            None => { return Err(format!("{} must be an array of API levels", key)); }
+        }
+    }
+    return Ok(api_levels);
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let mut content = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut content)).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        return content.parse();
+    }
+
+    /// Returns packages to install, choosing for each requirement the newest revision in the
+    /// repository satisfying it. An installed revision satisfying the manifest which is at least
+    /// as new is kept instead, as long as the repository lists it too.
+    pub fn requests(&self, sdk_repository: &SdkRepository, local_sdk: &LocalSdk) -> Result<Vec<Request>, String> {
+        let mut requests = Vec::new();
+        for &(ref id, req) in &[(PackageId::PlatformTools, &self.platform_tools), (PackageId::Tools, &self.tools), (PackageId::NdkBundle, &self.ndk_bundle)] {
+            if let Some(ref req) = *req {
+                let available: Vec<&dyn Package> = sdk_repository.find_all(id).into_iter().filter(|p| req.matches(&p.revision())).collect();
+                // Like the resolver, prefer revisions which can be installed on the running host.
+                let newest = match available.iter().find(|p| p.archive_for_host().is_some()).or(available.first()) {
+                    Some(package) => package.revision(),
+                    None => { return Err(format!("no revision of {} satisfies {}", id, req)); }
+                };
+                let installed = local_sdk.installed(id).and_then(|p| p.revision).filter(|r| req.matches(r));
+                let revision = installed.filter(|r| *r >= newest && available.iter().any(|p| p.revision() == *r)).unwrap_or(newest);
+                requests.push(Request { id: id.clone(), revision: Some(revision) });
+            }
+        }
+        for req in &self.build_tools {
+            let newest = sdk_repository.build_tools.iter().map(|b| b.revision.clone()).filter(|r| req.matches(r)).max();
+            match newest {
+                Some(revision) => requests.push(Request::from(PackageId::build_tools(revision))),
+                None => { return Err(format!("no revision of build-tools satisfies {}", req)); }
+            }
+        }
+        for api_level in &self.platforms {
+            requests.push(Request::from(PackageId::Platform(*api_level)));
+        }
+        for api_level in &self.sources {
+            requests.push(Request::from(PackageId::Source(*api_level)));
+        }
+        return Ok(requests);
+    }
+
+    /// Returns true if an installed package is listed in the manifest.
+    pub fn declares(&self, installed_package: &InstalledPackage) -> bool {
+        let revision_matches = |req: &VersionReq| installed_package.revision.as_ref().map(|r| req.matches(r)).unwrap_or(false);
+        match installed_package.id {
+            PackageId::PlatformTools => self.platform_tools.as_ref().map(&revision_matches).unwrap_or(false),
+            PackageId::Tools => self.tools.as_ref().map(&revision_matches).unwrap_or(false),
+            PackageId::NdkBundle => self.ndk_bundle.as_ref().map(&revision_matches).unwrap_or(false),
+            PackageId::BuildTools(ref revision) => self.build_tools.iter().any(|req| req.matches(revision)),
+            PackageId::Platform(api_level) => self.platforms.contains(&api_level),
+            PackageId::Source(api_level) => self.sources.contains(&api_level),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Manifest, String> {
+        let mut parser = toml::Parser::new(s);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let messages: Vec<String> = parser.errors.iter().map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{}:{}: {}", line + 1, col + 1, e.desc)
+                }).collect();
+                return Err(format!("invalid manifest:\n{}", messages.join("\n")));
+            }
+        };
+
+        let mut digest = Sha256::new();
+        digest.input_str(s);
+        let mut manifest = Manifest {
+            digest: digest.result_str(),
+            ..Manifest::default()
+        };
+        for (key, value) in &table {
+            match key.as_str() {
+                "platform-tools" => { manifest.platform_tools = Some(parse_version_req(key, value)?); }
+                "tools" => { manifest.tools = Some(parse_version_req(key, value)?); }
+                "ndk-bundle" => { manifest.ndk_bundle = Some(parse_version_req(key, value)?); }
+                "build-tools" => { manifest.build_tools = parse_version_reqs(key, value)?; }
+                "platforms" => { manifest.platforms = parse_api_levels(key, value)?; }
+                "sources" => { manifest.sources = parse_api_levels(key, value)?; }
+                _ => { return Err(format!("unknown key in manifest: {}", key)); }
+            }
+        }
+        return Ok(manifest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, VersionReq};
+
+    fn matches(req: &str, revision: &str) -> bool {
+        return req.parse::<VersionReq>().unwrap().matches(&revision.parse().unwrap());
+    }
+
+    #[test]
+    fn parse_version_reqs() {
+        assert_eq!(VersionReq::Any, "*".parse().unwrap());
+        assert_eq!(VersionReq::AtLeast("23.0.1".parse().unwrap()), ">= 23.0.1".parse().unwrap());
+        assert_eq!(VersionReq::Prefix(vec![23, 0]), "23.0.*".parse().unwrap());
+        assert_eq!(VersionReq::Exact("23.0.3".parse().unwrap()), " 23.0.3 ".parse().unwrap());
+        for req in &["", ">=", "23.x.*", ".*", "latest"] {
+            assert!(req.parse::<VersionReq>().is_err(), "{} was accepted", req);
+        }
+    }
+
+    #[test]
+    fn match_revisions() {
+        let cases = [
+            ("*", "24.0.0-rc1", true),
+            (">=23.0.1", "23.0.1", true),
+            (">=23.0.1", "23.1", true),
+            (">=23.0.1", "23", false),
+            (">=24", "24.0.0-rc1", false),
+            ("23.0.*", "23.0.3", true),
+            ("23.0.*", "23", true),
+            ("23.0.*", "23.1.0", false),
+            ("23.0.*", "23.0.3-rc1", false),
+            ("23.*", "23.4.1", true),
+            ("23.0.3", "23.0.3", true),
+            ("23", "23.0.0", true),
+            ("23.0.3", "23.0.2", false),
+        ];
+        for &(req, revision, expected) in &cases {
+            assert_eq!(expected, matches(req, revision), "{} matching {}", req, revision);
+        }
+    }
+
+    #[test]
+    fn parse_manifest() {
+        let manifest: Manifest = "platform-tools = true\ntools = \">=25.1\"\nbuild-tools = [\"23.0.*\", \"24.0.2\"]\nplatforms = [23, 24]\nsources = 23\n".parse().unwrap();
+
+        assert_eq!(Some(VersionReq::Any), manifest.platform_tools);
+        assert_eq!(Some(VersionReq::AtLeast("25.1".parse().unwrap())), manifest.tools);
+        assert_eq!(None, manifest.ndk_bundle);
+        assert_eq!(vec![VersionReq::Prefix(vec![23, 0]), VersionReq::Exact("24.0.2".parse().unwrap())], manifest.build_tools);
+        assert_eq!(vec![23, 24], manifest.platforms);
+        assert_eq!(vec![23], manifest.sources);
+        for invalid in &["tools = false", "build-tools = true", "platforms = [0]", "platforms = [\"23\"]", "unknown = 1"] {
+            assert!(invalid.parse::<Manifest>().is_err(), "{} was accepted", invalid);
+        }
+    }
+}
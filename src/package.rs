@@ -0,0 +1,266 @@
+use package_id::PackageId;
+use repository11::{Archive, BuildTool, Ndk, Platform, PlatformTool, Revision, SdkRepository, Source, Tool};
+use std::fmt;
+use std::path::PathBuf;
+
+/// A dependency on another package, optionally requiring a minimum revision of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub id: PackageId,
+    pub min_revision: Option<Revision>,
+}
+
+/// Common interface of every package type in the repository.
+pub trait Package {
+    fn id(&self) -> PackageId;
+    fn display_name(&self) -> String;
+    fn revision(&self) -> Revision;
+    fn uses_license(&self) -> Option<&str>;
+    fn description(&self) -> Option<&str>;
+    fn archives(&self) -> &[Archive];
+
+    fn api_level(&self) -> Option<u32> {
+        return None;
+    }
+
+    /// Android version of a platform, e.g. `6.0`.
+    fn version(&self) -> Option<&str> {
+        return None;
+    }
+
+    fn codename(&self) -> Option<&str> {
+        return None;
+    }
+
+    /// Path relative to the SDK root the package is installed into.
+    fn install_path(&self) -> PathBuf {
+        return self.id().relative_path();
+    }
+
+    fn dependencies(&self) -> Vec<Dependency> {
+        return Vec::new();
+    }
+
+    /// Returns the archive to install on the running host, if any.
+    fn archive_for_host(&self) -> Option<&Archive> {
+        return self.archives().iter().find(|a| a.matches_host());
+    }
+}
+
+impl<'a> fmt::Debug for dyn Package + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{} {}", self.id(), self.revision());
+    }
+}
+
+impl Package for Ndk {
+    fn id(&self) -> PackageId {
+        return PackageId::NdkBundle;
+    }
+
+    fn display_name(&self) -> String {
+        return "NDK".to_string();
+    }
+
+    fn revision(&self) -> Revision {
+        return Revision::from(self.revision);
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+}
+
+impl Package for Platform {
+    fn id(&self) -> PackageId {
+        return PackageId::Platform(self.api_level);
+    }
+
+    fn display_name(&self) -> String {
+        return format!("Android SDK Platform {}", self.api_level);
+    }
+
+    fn revision(&self) -> Revision {
+        return Revision::from(self.revision);
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+
+    fn api_level(&self) -> Option<u32> {
+        return Some(self.api_level);
+    }
+
+    fn version(&self) -> Option<&str> {
+        return Some(&self.version);
+    }
+
+    fn codename(&self) -> Option<&str> {
+        return self.codename.as_deref();
+    }
+
+    fn dependencies(&self) -> Vec<Dependency> {
+        return self.min_tools_rev.iter().map(|rev| Dependency { id: PackageId::Tools, min_revision: Some(rev.clone()) }).collect();
+    }
+}
+
+impl Package for Source {
+    fn id(&self) -> PackageId {
+        return PackageId::Source(self.api_level);
+    }
+
+    fn display_name(&self) -> String {
+        return format!("Sources for Android {}", self.api_level);
+    }
+
+    fn revision(&self) -> Revision {
+        return Revision::from(self.revision);
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+
+    fn api_level(&self) -> Option<u32> {
+        return Some(self.api_level);
+    }
+}
+
+impl Package for BuildTool {
+    fn id(&self) -> PackageId {
+        return PackageId::build_tools(self.revision.clone());
+    }
+
+    fn display_name(&self) -> String {
+        return format!("Android SDK Build-Tools {}", self.revision);
+    }
+
+    fn revision(&self) -> Revision {
+        return self.revision.clone();
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+}
+
+impl Package for PlatformTool {
+    fn id(&self) -> PackageId {
+        return PackageId::PlatformTools;
+    }
+
+    fn display_name(&self) -> String {
+        return "Android SDK Platform-Tools".to_string();
+    }
+
+    fn revision(&self) -> Revision {
+        return self.revision.clone();
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+}
+
+impl Package for Tool {
+    fn id(&self) -> PackageId {
+        return PackageId::Tools;
+    }
+
+    fn display_name(&self) -> String {
+        return "Android SDK Tools".to_string();
+    }
+
+    fn revision(&self) -> Revision {
+        return self.revision.clone();
+    }
+
+    fn uses_license(&self) -> Option<&str> {
+        return self.uses_license.as_deref();
+    }
+
+    fn description(&self) -> Option<&str> {
+        return self.description.as_deref();
+    }
+
+    fn archives(&self) -> &[Archive] {
+        return &self.archives;
+    }
+
+    fn dependencies(&self) -> Vec<Dependency> {
+        return self.min_platform_tools_rev.iter().map(|rev| Dependency { id: PackageId::PlatformTools, min_revision: Some(rev.clone()) }).collect();
+    }
+}
+
+impl SdkRepository {
+    /// Iterates over packages of every type in the repository.
+    pub fn packages<'a>(&'a self) -> Box<dyn Iterator<Item=&'a dyn Package> + 'a> {
+        return Box::new(self.ndks.iter().map(|p| p as &dyn Package)
+                        .chain(self.platforms.iter().map(|p| p as &dyn Package))
+                        .chain(self.sources.iter().map(|p| p as &dyn Package))
+                        .chain(self.build_tools.iter().map(|p| p as &dyn Package))
+                        .chain(self.platform_tools.iter().map(|p| p as &dyn Package))
+                        .chain(self.tools.iter().map(|p| p as &dyn Package)));
+    }
+
+    /// Returns every revision of the package identified by `id`, newest first.
+    pub fn find_all(&self, id: &PackageId) -> Vec<&dyn Package> {
+        let mut found: Vec<&dyn Package> = self.packages().filter(|p| p.id() == *id).collect();
+        found.sort_by_key(|p| p.revision());
+        found.reverse();
+        return found;
+    }
+
+    /// Returns the package identified by `id`, picking the newest revision when the repository
+    /// lists several of them.
+    pub fn find(&self, id: &PackageId) -> Option<&dyn Package> {
+        let mut found: Option<&dyn Package> = None;
+        for package in self.packages() {
+            if package.id() == *id && found.map(|f| f.revision() < package.revision()).unwrap_or(true) {
+                found = Some(package);
+            }
+        }
+        return found;
+    }
+}
@@ -0,0 +1,167 @@
+use package::Dependency;
+use repository11::Revision;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Identifies a package the same way as `sdkmanager` does, e.g. `platforms;android-23` or
+/// `system-images;android-23;google_apis;x86`. Each `;`-separated component is also a component
+/// of the path the package is installed into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PackageId {
+    NdkBundle,
+    PlatformTools,
+    Tools,
+    Platform(u32),
+    Source(u32),
+    /// Build tools, with the minor and micro versions of their revision always present. Use
+    /// `PackageId::build_tools` to build one from any revision.
+    BuildTools(Revision),
+    SystemImage { api_level: u32, tag: String, abi: String },
+    AddOn { vendor: String, name: String, api_level: u32 },
+}
+
+impl PackageId {
+    /// Build tools of `revision`, whose missing minor and micro versions are taken as 0 so that
+    /// equal revisions name the same package and install path: `build-tools;23` is
+    /// `build-tools;23.0.0`.
+    pub fn build_tools(revision: Revision) -> PackageId {
+        return PackageId::BuildTools(Revision {
+            minor: Some(revision.minor.unwrap_or(0)),
+            micro: Some(revision.micro.unwrap_or(0)),
+            ..revision
+        });
+    }
+
+    /// Path relative to the SDK root the package is installed into.
+    pub fn relative_path(&self) -> PathBuf {
+        let mut path = PathBuf::new();
+        for component in self.to_string().split(';') {
+            path.push(component);
+        }
+        return path;
+    }
+
+    /// Dependencies implied by the kind of package alone: add-ons and system images are built
+    /// on top of the platform of the same API level.
+    pub fn implied_dependencies(&self) -> Vec<Dependency> {
+        match *self {
+            PackageId::SystemImage { api_level, .. } | PackageId::AddOn { api_level, .. } => {
+                vec![Dependency { id: PackageId::Platform(api_level), min_revision: None }]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn parse_api_level(s: &str, id: &str) -> Result<u32, String> {
+    if let Some(api_level) = s.strip_prefix("android-") {
+        if let Ok(api_level) = api_level.parse::<u32>() {
+            return Ok(api_level);
+        }
+    }
+    return Err(format!("invalid API level in package id {}: {}", id, s));
+}
+
+impl FromStr for PackageId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PackageId, String> {
+        let components: Vec<&str> = s.split(';').collect();
+        match (components[0], components.len()) {
+            ("ndk-bundle", 1) => Ok(PackageId::NdkBundle),
+            ("platform-tools", 1) => Ok(PackageId::PlatformTools),
+            ("tools", 1) => Ok(PackageId::Tools),
+            ("platforms", 2) => Ok(PackageId::Platform(parse_api_level(components[1], s)?)),
+            ("sources", 2) => Ok(PackageId::Source(parse_api_level(components[1], s)?)),
+            ("build-tools", 2) => Ok(PackageId::build_tools(components[1].parse()?)),
+            ("system-images", 4) => {
+                Ok(PackageId::SystemImage {
+                    api_level: parse_api_level(components[1], s)?,
+                    tag: components[2].to_string(),
+                    abi: components[3].to_string(),
+                })
+            }
+            ("add-ons", 2) if components[1].starts_with("addon-") => {
+                // addon-<name>-<vendor>-<api-level>, e.g. addon-google_apis-google-23
+                let parts: Vec<&str> = components[1]["addon-".len()..].rsplitn(3, '-').collect();
+                if parts.len() != 3 {
+                    return Err(format!("invalid add-on package id: {}", s));
+                }
+                Ok(PackageId::AddOn {
+                    vendor: parts[1].to_string(),
+                    name: parts[2].to_string(),
+                    api_level: parts[0].parse::<u32>().map_err(|_| format!("invalid add-on package id: {}", s))?,
+                })
+            }
+            _ => Err(format!("unknown package id: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PackageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PackageId::NdkBundle => write!(f, "ndk-bundle"),
+            PackageId::PlatformTools => write!(f, "platform-tools"),
+            PackageId::Tools => write!(f, "tools"),
+            PackageId::Platform(api_level) => write!(f, "platforms;android-{}", api_level),
+            PackageId::Source(api_level) => write!(f, "sources;android-{}", api_level),
+            PackageId::BuildTools(ref revision) => write!(f, "build-tools;{}", revision),
+            PackageId::SystemImage { api_level, ref tag, ref abi } => write!(f, "system-images;android-{};{};{}", api_level, tag, abi),
+            PackageId::AddOn { ref vendor, ref name, api_level } => write!(f, "add-ons;addon-{}-{}-{}", name, vendor, api_level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PackageId;
+    use std::path::PathBuf;
+
+    fn assert_round_trip(id: &str, expected: PackageId, path: &[&str]) {
+        let parsed: PackageId = id.parse().unwrap();
+        assert_eq!(expected, parsed);
+        assert_eq!(id, parsed.to_string());
+        assert_eq!(path.iter().collect::<PathBuf>(), parsed.relative_path());
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip("ndk-bundle", PackageId::NdkBundle, &["ndk-bundle"]);
+        assert_round_trip("platform-tools", PackageId::PlatformTools, &["platform-tools"]);
+        assert_round_trip("tools", PackageId::Tools, &["tools"]);
+        assert_round_trip("platforms;android-23", PackageId::Platform(23), &["platforms", "android-23"]);
+        assert_round_trip("sources;android-23", PackageId::Source(23), &["sources", "android-23"]);
+        assert_round_trip("build-tools;23.0.3", PackageId::build_tools("23.0.3".parse().unwrap()), &["build-tools", "23.0.3"]);
+        assert_round_trip("build-tools;24.0.0-rc1", PackageId::build_tools("24.0.0-rc1".parse().unwrap()), &["build-tools", "24.0.0-rc1"]);
+        assert_round_trip("system-images;android-23;google_apis;x86", PackageId::SystemImage {
+            api_level: 23,
+            tag: "google_apis".to_string(),
+            abi: "x86".to_string(),
+        }, &["system-images", "android-23", "google_apis", "x86"]);
+        assert_round_trip("add-ons;addon-google_apis-google-23", PackageId::AddOn {
+            vendor: "google".to_string(),
+            name: "google_apis".to_string(),
+            api_level: 23,
+        }, &["add-ons", "addon-google_apis-google-23"]);
+    }
+
+    #[test]
+    fn fill_in_build_tools_revision() {
+        let short: PackageId = "build-tools;23".parse().unwrap();
+        let full: PackageId = "build-tools;23.0.0".parse().unwrap();
+
+        assert_eq!(full, short);
+        assert_eq!("build-tools;23.0.0", short.to_string());
+        assert_eq!(full.relative_path(), short.relative_path());
+        assert_eq!(full, PackageId::build_tools("23".parse().unwrap()));
+    }
+
+    #[test]
+    fn reject_invalid_ids() {
+        for id in &["", "platforms", "platforms;23", "platforms;android-x", "build-tools;a.b", "system-images;android-23;x86", "add-ons;addon-google", "tools;1"] {
+            assert!(id.parse::<PackageId>().is_err(), "{} was accepted", id);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use downloader::{Progress, ProgressObserver};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std;
+
+/// Shows the combined progress of running downloads on stdout: a progress bar redrawn in place
+/// when stdout is a terminal, otherwise a log line every few seconds.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    tty: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    downloads: BTreeMap<String, Download>,
+    last_report: Option<Instant>,
+    bar_visible: bool,
+}
+
+#[derive(Debug)]
+struct Download {
+    downloaded: u64,
+    total: Option<u64>,
+    rate: f64,
+}
+
+impl Default for ProgressReporter {
+    fn default() -> ProgressReporter {
+        ProgressReporter::new()
+    }
+}
+
+impl ProgressReporter {
+    pub fn new() -> ProgressReporter {
+        ProgressReporter {
+            tty: stdout_is_tty(),
+            state: Mutex::new(State {
+                downloads: BTreeMap::new(),
+                last_report: None,
+                bar_visible: false,
+            }),
+        }
+    }
+
+    /// Erases the progress bar so that other output does not get mixed with it. It is drawn
+    /// again on the next progress report.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.clear_bar(&mut state);
+    }
+
+    fn clear_bar(&self, state: &mut State) {
+        if state.bar_visible {
+            let mut stdout = std::io::stdout();
+            let _ = write!(stdout, "\r\x1b[K");
+            let _ = stdout.flush();
+            state.bar_visible = false;
+        }
+    }
+
+    fn report(&self, state: &mut State) {
+        let downloaded: u64 = state.downloads.values().map(|d| d.downloaded).sum();
+        let rate: f64 = state.downloads.values().map(|d| d.rate).sum();
+        // The total is only known when every running download knows its own.
+        let total = state.downloads.values().try_fold(0, |sum, d| d.total.map(|t| sum + t));
+
+        let mut line = String::new();
+        match total {
+            Some(total) if total > 0 => {
+                let ratio = (downloaded as f64 / total as f64).min(1.0);
+                if self.tty {
+                    let width = 30;
+                    let filled = (ratio * width as f64) as usize;
+                    line.push_str(&format!("[{}{}] ", "#".repeat(filled), " ".repeat(width - filled)));
+                }
+                line.push_str(&format!("{:3}% {} / {}", (ratio * 100.0) as u32, format_size(downloaded), format_size(total)));
+            }
+            _ => { line.push_str(&format_size(downloaded)); }
+        }
+        line.push_str(&format!(", {}/s", format_size(rate as u64)));
+        if let Some(total) = total {
+            if rate > 0.0 && total > downloaded {
+                line.push_str(&format!(", ETA {}", format_eta(Duration::from_secs(((total - downloaded) as f64 / rate) as u64))));
+            }
+        }
+        if state.downloads.len() > 1 {
+            line.push_str(&format!(" ({} downloads)", state.downloads.len()));
+        }
+
+        let mut stdout = std::io::stdout();
+        if self.tty {
+            let _ = write!(stdout, "\r\x1b[K{}", line);
+            let _ = stdout.flush();
+            state.bar_visible = true;
+        } else {
+            println!("Downloading: {}", line);
+        }
+    }
+}
+
+impl ProgressObserver for ProgressReporter {
+    fn progress(&self, progress: &Progress) {
+        let mut state = self.state.lock().unwrap();
+        state.downloads.insert(progress.url.to_string(), Download {
+            downloaded: progress.downloaded,
+            total: progress.total,
+            rate: progress.rate(),
+        });
+        if self.tty {
+            if state.last_report.map(|t| t.elapsed() >= Duration::from_millis(100)).unwrap_or(true) {
+                state.last_report = Some(Instant::now());
+                self.report(&mut state);
+            }
+        } else {
+            // Log lines start once a download has run for a while, so short downloads only log
+            // that they finished.
+            match state.last_report {
+                Some(t) if t.elapsed() >= Duration::from_secs(5) => {
+                    state.last_report = Some(Instant::now());
+                    self.report(&mut state);
+                }
+                Some(_) => {}
+                None => { state.last_report = Some(Instant::now()); }
+            }
+        }
+    }
+
+    fn finished(&self, url: &str, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.downloads.remove(url);
+        self.clear_bar(&mut state);
+        if success {
+            println!("Downloaded {}", url);
+        }
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < units.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{} {}", bytes, units[unit]);
+    }
+    return format!("{:.1} {}", size, units[unit]);
+}
+
+fn format_eta(eta: Duration) -> String {
+    let seconds = eta.as_secs();
+    if seconds >= 60 * 60 {
+        format!("{}h{:02}m", seconds / (60 * 60), seconds / 60 % 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(unix)]
+fn stdout_is_tty() -> bool {
+    return unsafe { libc::isatty(libc::STDOUT_FILENO) } != 0;
+}
+
+#[cfg(not(unix))]
+fn stdout_is_tty() -> bool {
+    return false;
+}
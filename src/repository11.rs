@@ -1,10 +1,22 @@
+use checksum::{Algorithm, Checksum};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::str::FromStr;
+use xml::writer::{EventWriter, XmlEvent};
+use xml::writer::Error as WriteError;
+use xml::writer::Result as WriteResult;
+use xmlhelper;
 use xmlhelper::Event;
 
 pub static XML_URL_BASE: &'static str = "https://dl.google.com/android/repository";
 pub static XML_URL: &'static str = "https://dl.google.com/android/repository/repository-11.xml";
+pub static XML_FILE: &'static str = "repository-11.xml";
+pub static XML_NAMESPACE: &'static str = "http://schemas.android.com/sdk/android/repository/11";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SdkRepository {
     pub licenses: HashMap<String, String>,
     pub ndks: Vec<Ndk>,
@@ -12,59 +24,160 @@ pub struct SdkRepository {
     pub sources: Vec<Source>,
     pub build_tools: Vec<BuildTool>,
     pub platform_tools: Vec<PlatformTool>,
+    pub tools: Vec<Tool>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ndk {
     pub revision: u32,
     pub uses_license: Option<String>,
+    pub description: Option<String>,
     pub archives: Vec<Archive>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Platform {
+    pub version: String,
     pub api_level: u32,
+    pub codename: Option<String>,
     pub revision: u32,
+    pub layoutlib_api: Option<u32>,
+    pub min_tools_rev: Option<Revision>,
+    pub description: Option<String>,
     pub archives: Vec<Archive>,
     pub uses_license: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Archive {
-    pub checksum: String,
+    pub size: u64,
+    /// Checksums of supported algorithms; those of other algorithms are ignored.
+    pub checksums: Vec<Checksum>,
     pub url: String,
     pub host_os: Option<OsType>,
     pub host_bits: Option<BitSize>,
 }
 
+impl SdkRepository {
+    /// Makes relative archive URLs absolute, resolving them against `base_url`, the directory
+    /// the manifest was downloaded from.
+    pub fn resolve_urls(&mut self, base_url: &str) {
+        let archives = self.ndks.iter_mut().flat_map(|p| p.archives.iter_mut())
+            .chain(self.platforms.iter_mut().flat_map(|p| p.archives.iter_mut()))
+            .chain(self.sources.iter_mut().flat_map(|p| p.archives.iter_mut()))
+            .chain(self.build_tools.iter_mut().flat_map(|p| p.archives.iter_mut()))
+            .chain(self.platform_tools.iter_mut().flat_map(|p| p.archives.iter_mut()))
+            .chain(self.tools.iter_mut().flat_map(|p| p.archives.iter_mut()));
+        for archive in archives {
+            archive.url = archive.url_relative_to(base_url);
+        }
+    }
+}
+
 impl Archive {
     pub fn absolute_url(&self) -> String {
-        return format!("{}/{}", XML_URL_BASE, self.url);
+        return self.url_relative_to(XML_URL_BASE);
+    }
+
+    pub fn url_relative_to(&self, base_url: &str) -> String {
+        if self.url.contains("://") {
+            return self.url.clone();
+        }
+        return format!("{}/{}", base_url.trim_end_matches('/'), self.url);
+    }
+
+    /// Returns true if the archive can be installed on the running host.
+    pub fn matches_host(&self) -> bool {
+        return self.host_os.map(|os| os == OsType::current()).unwrap_or(true) && self.host_bits.map(|bits| bits == BitSize::current()).unwrap_or(true);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OsType { Linux, Macosx, Windows }
 
-#[derive(Debug)]
+impl OsType {
+    pub fn current() -> OsType {
+        if cfg!(target_os = "macos") {
+            OsType::Macosx
+        } else if cfg!(target_os = "windows") {
+            OsType::Windows
+        } else {
+            OsType::Linux
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OsType::Linux => "linux",
+            OsType::Macosx => "macosx",
+            OsType::Windows => "windows",
+        }
+    }
+}
+
+impl FromStr for OsType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OsType, String> {
+        match s {
+            "linux" => Ok(OsType::Linux),
+            "macosx" => Ok(OsType::Macosx),
+            "windows" => Ok(OsType::Windows),
+            _ => Err(format!("unknown host-os value: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BitSize { Bit32, Bit64 }
 
-#[derive(Debug)]
+impl BitSize {
+    pub fn current() -> BitSize {
+        if cfg!(target_pointer_width = "64") {
+            BitSize::Bit64
+        } else {
+            BitSize::Bit32
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            BitSize::Bit32 => "32",
+            BitSize::Bit64 => "64",
+        }
+    }
+}
+
+impl FromStr for BitSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BitSize, String> {
+        match s {
+            "32" => Ok(BitSize::Bit32),
+            "64" => Ok(BitSize::Bit64),
+            _ => Err(format!("unknown host-bits value: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Source {
     pub api_level: u32,
     pub revision: u32,
+    pub description: Option<String>,
     pub archives: Vec<Archive>,
     pub uses_license: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BuildTool {
     pub revision: Revision,
     pub uses_license: Option<String>,
+    pub description: Option<String>,
     pub archives: Vec<Archive>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Revision {
     pub major: u32,
     pub minor: Option<u32>,
@@ -72,10 +185,112 @@ pub struct Revision {
     pub preview: Option<u32>,
 }
 
-#[derive(Debug)]
+impl Revision {
+    // A missing minor or micro version is equivalent to 0, and a final release is newer than any preview.
+    fn key(&self) -> (u32, u32, u32, bool, u32) {
+        return (self.major, self.minor.unwrap_or(0), self.micro.unwrap_or(0), self.preview.is_none(), self.preview.unwrap_or(0));
+    }
+}
+
+impl From<u32> for Revision {
+    fn from(major: u32) -> Revision {
+        Revision {
+            major: major,
+            minor: None,
+            micro: None,
+            preview: None,
+        }
+    }
+}
+
+impl PartialEq for Revision {
+    fn eq(&self, other: &Revision) -> bool {
+        return self.key() == other.key();
+    }
+}
+
+impl Eq for Revision {}
+
+impl PartialOrd for Revision {
+    fn partial_cmp(&self, other: &Revision) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl Ord for Revision {
+    fn cmp(&self, other: &Revision) -> Ordering {
+        return self.key().cmp(&other.key());
+    }
+}
+
+impl Hash for Revision {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl FromStr for Revision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Revision, String> {
+        // Previews are written as "24.0.0-rc1" in package ids and "24.0.0 rc1" in source.properties.
+        let (version, preview) = match s.find("-rc").or_else(|| s.find(" rc")) {
+            Some(i) => (&s[..i], Some(&s[i + 3..])),
+            None => (s, None),
+        };
+        let mut components = Vec::new();
+        for component in version.split('.') {
+            match component.parse::<u32>() {
+                Ok(n) => components.push(n),
+                Err(_) => { return Err(format!("invalid revision: {}", s)); }
+            }
+        }
+        if components.len() > 3 {
+            return Err(format!("invalid revision: {}", s));
+        }
+        let preview = match preview {
+            Some(p) => Some(p.parse::<u32>().map_err(|_| format!("invalid revision: {}", s))?),
+            None => None,
+        };
+        return Ok(Revision {
+            major: components[0],
+            minor: components.get(1).cloned(),
+            micro: components.get(2).cloned(),
+            preview: preview,
+        });
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.major)?;
+        if let Some(minor) = self.minor {
+            write!(f, ".{}", minor)?;
+        }
+        if let Some(micro) = self.micro {
+            write!(f, ".{}", micro)?;
+        }
+        if let Some(preview) = self.preview {
+            write!(f, "-rc{}", preview)?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct PlatformTool {
     pub revision: Revision,
     pub uses_license: Option<String>,
+    pub description: Option<String>,
+    pub archives: Vec<Archive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tool {
+    pub revision: Revision,
+    pub min_platform_tools_rev: Option<Revision>,
+    pub uses_license: Option<String>,
+    pub description: Option<String>,
     pub archives: Vec<Archive>,
 }
 
@@ -87,6 +302,7 @@ pub fn parse_sdk_repository<I: Iterator<Item=Event>>(mut stream: &mut I) -> Resu
         sources: Vec::new(),
         build_tools: Vec::new(),
         platform_tools: Vec::new(),
+        tools: Vec::new(),
     };
     match stream.next() {
         Some(Event::StartElement { ref local_name, .. }) if local_name == "sdk-repository" => {
@@ -100,22 +316,24 @@ pub fn parse_sdk_repository<I: Iterator<Item=Event>>(mut stream: &mut I) -> Resu
         match stream.next() {
             Some(Event::StartElement { local_name, attributes }) => {
                 if local_name == "license" {
-                    let license = try!(parse_string(&mut stream, local_name));
+                    let license = parse_string(&mut stream, local_name)?;
                     if let Some(id) = attributes.get("id") {
                         sdk_repository.licenses.insert(id.clone(), license);
                     } else {
                         return Err("license element does not have id attribute".to_string());
                     }
                 } else if local_name == "ndk" {
-                    sdk_repository.ndks.push(try!(parse_ndk(&mut stream)));
+                    sdk_repository.ndks.push(parse_ndk(&mut stream)?);
                 } else if local_name == "platform" {
-                    sdk_repository.platforms.push(try!(parse_platform(&mut stream)));
+                    sdk_repository.platforms.push(parse_platform(&mut stream)?);
                 } else if local_name == "source" {
-                    sdk_repository.sources.push(try!(parse_source(&mut stream)));
+                    sdk_repository.sources.push(parse_source(&mut stream)?);
                 } else if local_name == "build-tool" {
-                    sdk_repository.build_tools.push(try!(parse_build_tool(&mut stream)));
+                    sdk_repository.build_tools.push(parse_build_tool(&mut stream)?);
                 } else if local_name == "platform-tool" {
-                    sdk_repository.platform_tools.push(try!(parse_platform_tool(&mut stream)));
+                    sdk_repository.platform_tools.push(parse_platform_tool(&mut stream)?);
+                } else if local_name == "tool" {
+                    sdk_repository.tools.push(parse_tool(&mut stream)?);
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -124,12 +342,12 @@ pub fn parse_sdk_repository<I: Iterator<Item=Event>>(mut stream: &mut I) -> Resu
                 }
             }
             Some(_) => {}
-            None => { return Err(format!("parse error during sdk-repository")); }
+            None => { return Err("parse error during sdk-repository".to_string()); }
         }
     }
 }
 
-fn parse_string<I: Iterator<Item=Event>>(mut stream: &mut I, name: String) -> Result<String, String> {
+fn parse_string<I: Iterator<Item=Event>>(stream: &mut I, name: String) -> Result<String, String> {
     let mut result = String::new();
 
     loop {
@@ -153,6 +371,7 @@ fn parse_ndk<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Ndk, String>
     let mut ndk = Ndk {
         revision: 0,
         uses_license: None,
+        description: None,
         archives: Vec::new(),
     };
 
@@ -160,11 +379,13 @@ fn parse_ndk<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Ndk, String>
         match stream.next() {
             Some(Event::StartElement { local_name, attributes }) => {
                 if local_name == "revision" {
-                    ndk.revision = try!(parse_u32(&mut stream, local_name));
+                    ndk.revision = parse_u32(&mut stream, local_name)?;
                 } else if local_name == "uses-license" {
-                    ndk.uses_license = Some(try!(convert_uses_license(attributes)));
+                    ndk.uses_license = Some(convert_uses_license(attributes)?);
+                } else if local_name == "description" {
+                    ndk.description = Some(parse_string(&mut stream, local_name)?);
                 } else if local_name == "archives" {
-                    ndk.archives = try!(parse_archives(&mut stream));
+                    ndk.archives = parse_archives(&mut stream)?;
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -188,10 +409,15 @@ fn convert_uses_license(attributes: HashMap<String, String>) -> Result<String, S
 
 fn parse_platform<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Platform, String> {
     let mut platform = Platform {
+        version: String::new(),
         archives: Vec::new(),
         uses_license: None,
         api_level: 0,
+        codename: None,
         revision: 0,
+        layoutlib_api: None,
+        min_tools_rev: None,
+        description: None,
     };
 
     loop {
@@ -204,11 +430,21 @@ fn parse_platform<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Platfor
                         return Err("uses-license element does not have ref attribute".to_string());
                     }
                 } else if local_name == "api-level" {
-                    platform.api_level = try!(parse_u32(&mut stream, local_name));
+                    platform.api_level = parse_u32(&mut stream, local_name)?;
                 } else if local_name == "revision" {
-                    platform.revision = try!(parse_u32(&mut stream, local_name));
+                    platform.revision = parse_u32(&mut stream, local_name)?;
+                } else if local_name == "version" {
+                    platform.version = parse_string(&mut stream, local_name)?;
+                } else if local_name == "codename" {
+                    platform.codename = Some(parse_string(&mut stream, local_name)?);
+                } else if local_name == "layoutlib" {
+                    platform.layoutlib_api = Some(parse_layoutlib(&mut stream)?);
+                } else if local_name == "min-tools-rev" {
+                    platform.min_tools_rev = Some(parse_revision(&mut stream, local_name)?);
+                } else if local_name == "description" {
+                    platform.description = Some(parse_string(&mut stream, local_name)?);
                 } else if local_name == "archives" {
-                    platform.archives = try!(parse_archives(&mut stream));
+                    platform.archives = parse_archives(&mut stream)?;
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -222,7 +458,7 @@ fn parse_platform<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Platfor
     }
 }
 
-fn parse_u32<I: Iterator<Item=Event>>(mut stream: &mut I, name: String) -> Result<u32, String> {
+fn parse_u32<I: Iterator<Item=Event>>(stream: &mut I, name: String) -> Result<u32, String> {
     let mut result = 0;
 
     loop {
@@ -246,6 +482,51 @@ fn parse_u32<I: Iterator<Item=Event>>(mut stream: &mut I, name: String) -> Resul
     }
 }
 
+fn parse_u64<I: Iterator<Item=Event>>(stream: &mut I, name: String) -> Result<u64, String> {
+    let mut result = 0;
+
+    loop {
+        match stream.next() {
+            Some(Event::Text { text }) => {
+                if let Ok(i) = text.parse::<u64>() {
+                    result = i;
+                } else {
+                    return Err(format!("non-positive-integer {} was found: {}", name, text));
+                }
+            }
+            Some(Event::EndElement { local_name }) => {
+                if local_name == name {
+                    return Ok(result);
+                } else {
+                    return Err(format!("unexpected end element while parsing {}: {}", name, local_name));
+                }
+            }
+            e => { return Err(format!("parse error during {}: {:?}", name, e)); }
+        }
+    }
+}
+
+fn parse_layoutlib<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<u32, String> {
+    let mut api = 0;
+
+    loop {
+        match stream.next() {
+            Some(Event::StartElement { local_name, .. }) => {
+                if local_name == "api" {
+                    api = parse_u32(&mut stream, local_name)?;
+                }
+            }
+            Some(Event::EndElement { local_name }) => {
+                if local_name == "layoutlib" {
+                    return Ok(api);
+                }
+            }
+            Some(_) => {}
+            None => { return Err("parse error during layoutlib".to_string()); }
+        }
+    }
+}
+
 fn parse_archives<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Vec<Archive>, String> {
     let mut result = Vec::new();
 
@@ -253,7 +534,7 @@ fn parse_archives<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Vec<Arc
         match stream.next() {
             Some(Event::StartElement { local_name, .. }) => {
                 if local_name == "archive" {
-                    result.push(try!(parse_archive(&mut stream)));
+                    result.push(parse_archive(&mut stream)?);
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -269,7 +550,8 @@ fn parse_archives<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Vec<Arc
 
 fn parse_archive<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Archive, String> {
     let mut result = Archive {
-        checksum: String::new(),
+        size: 0,
+        checksums: Vec::new(),
         url: String::new(),
         host_os: None,
         host_bits: None,
@@ -277,15 +559,21 @@ fn parse_archive<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Archive,
 
     loop {
         match stream.next() {
-            Some(Event::StartElement { local_name, .. }) => {
-                if local_name == "checksum" {
-                    result.checksum = try!(parse_string(&mut stream, local_name));
+            Some(Event::StartElement { local_name, attributes }) => {
+                if local_name == "size" {
+                    result.size = parse_u64(&mut stream, local_name)?;
+                } else if local_name == "checksum" {
+                    let digest = parse_string(&mut stream, local_name)?;
+                    // The type is required by the schema and was always sha1.
+                    if let Ok(algorithm) = attributes.get("type").map(|t| t.as_str()).unwrap_or("sha1").parse::<Algorithm>() {
+                        result.checksums.push(Checksum::new(algorithm, &digest));
+                    }
                 } else if local_name == "url" {
-                    result.url = try!(parse_string(&mut stream, local_name));
+                    result.url = parse_string(&mut stream, local_name)?;
                 } else if local_name == "host-os" {
-                    result.host_os = Some(try!(parse_host_os(&mut stream)));
+                    result.host_os = Some(parse_host_os(&mut stream)?);
                 } else if local_name == "host-bits" {
-                    result.host_bits = Some(try!(parse_host_bits(&mut stream)));
+                    result.host_bits = Some(parse_host_bits(&mut stream)?);
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -299,7 +587,7 @@ fn parse_archive<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Archive,
     }
 }
 
-fn parse_host_os<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<OsType, String> {
+fn parse_host_os<I: Iterator<Item=Event>>(stream: &mut I) -> Result<OsType, String> {
     let mut result = OsType::Linux;
 
     loop {
@@ -327,7 +615,7 @@ fn parse_host_os<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<OsType,
     }
 }
 
-fn parse_host_bits<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<BitSize, String> {
+fn parse_host_bits<I: Iterator<Item=Event>>(stream: &mut I) -> Result<BitSize, String> {
     let mut result = BitSize::Bit32;
 
     loop {
@@ -359,6 +647,7 @@ fn parse_source<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Source, S
         uses_license: None,
         api_level: 0,
         revision: 0,
+        description: None,
     };
 
     loop {
@@ -371,11 +660,13 @@ fn parse_source<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Source, S
                         return Err("uses-license element does not have ref attribute".to_string());
                     }
                 } else if local_name == "api-level" {
-                    source.api_level = try!(parse_u32(&mut stream, local_name));
+                    source.api_level = parse_u32(&mut stream, local_name)?;
                 } else if local_name == "revision" {
-                    source.revision = try!(parse_u32(&mut stream, local_name));
+                    source.revision = parse_u32(&mut stream, local_name)?;
+                } else if local_name == "description" {
+                    source.description = Some(parse_string(&mut stream, local_name)?);
                 } else if local_name == "archives" {
-                    source.archives = try!(parse_archives(&mut stream));
+                    source.archives = parse_archives(&mut stream)?;
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -399,17 +690,20 @@ fn parse_build_tool<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Build
         },
         archives: Vec::new(),
         uses_license: None,
+        description: None,
     };
 
     loop {
         match stream.next() {
             Some(Event::StartElement { local_name, attributes }) => {
                 if local_name == "revision" {
-                    build_tool.revision = try!(parse_revision(&mut stream));
+                    build_tool.revision = parse_revision(&mut stream, local_name)?;
                 } else if local_name == "uses-license" {
-                    build_tool.uses_license = Some(try!(convert_uses_license(attributes)));
+                    build_tool.uses_license = Some(convert_uses_license(attributes)?);
+                } else if local_name == "description" {
+                    build_tool.description = Some(parse_string(&mut stream, local_name)?);
                 } else if local_name == "archives" {
-                    build_tool.archives = try!(parse_archives(&mut stream));
+                    build_tool.archives = parse_archives(&mut stream)?;
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -423,7 +717,7 @@ fn parse_build_tool<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Build
     }
 }
 
-fn parse_revision<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Revision, String> {
+fn parse_revision<I: Iterator<Item=Event>>(mut stream: &mut I, name: String) -> Result<Revision, String> {
     let mut revision = Revision {
         major: 0,
         minor: None,
@@ -435,22 +729,22 @@ fn parse_revision<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Revisio
         match stream.next() {
             Some(Event::StartElement { local_name, .. }) => {
                 if local_name == "major" {
-                    revision.major = try!(parse_u32(&mut stream, local_name));
+                    revision.major = parse_u32(&mut stream, local_name)?;
                 } else if local_name == "minor" {
-                    revision.minor = Some(try!(parse_u32(&mut stream, local_name)));
+                    revision.minor = Some(parse_u32(&mut stream, local_name)?);
                 } else if local_name == "micro" {
-                    revision.micro = Some(try!(parse_u32(&mut stream, local_name)));
+                    revision.micro = Some(parse_u32(&mut stream, local_name)?);
                 } else if local_name == "preview" {
-                    revision.preview = Some(try!(parse_u32(&mut stream, local_name)));
+                    revision.preview = Some(parse_u32(&mut stream, local_name)?);
                 }
             }
             Some(Event::EndElement { local_name }) => {
-                if local_name == "revision" {
+                if local_name == name {
                     return Ok(revision);
                 }
             }
             Some(_) => {}
-            None => { return Err("parse error during revision".to_string()); }
+            None => { return Err(format!("parse error during {}", name)); }
         }
     }
 }
@@ -465,17 +759,20 @@ fn parse_platform_tool<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Pl
         },
         archives: Vec::new(),
         uses_license: None,
+        description: None,
     };
 
     loop {
         match stream.next() {
             Some(Event::StartElement { local_name, attributes }) => {
                 if local_name == "revision" {
-                    platform_tool.revision = try!(parse_revision(&mut stream));
+                    platform_tool.revision = parse_revision(&mut stream, local_name)?;
                 } else if local_name == "uses-license" {
-                    platform_tool.uses_license = Some(try!(convert_uses_license(attributes)));
+                    platform_tool.uses_license = Some(convert_uses_license(attributes)?);
+                } else if local_name == "description" {
+                    platform_tool.description = Some(parse_string(&mut stream, local_name)?);
                 } else if local_name == "archives" {
-                    platform_tool.archives = try!(parse_archives(&mut stream));
+                    platform_tool.archives = parse_archives(&mut stream)?;
                 }
             }
             Some(Event::EndElement { local_name }) => {
@@ -488,3 +785,209 @@ fn parse_platform_tool<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Pl
         }
     }
 }
+
+fn parse_tool<I: Iterator<Item=Event>>(mut stream: &mut I) -> Result<Tool, String> {
+    let mut tool = Tool {
+        revision: Revision {
+            major: 0,
+            minor: None,
+            micro: None,
+            preview: None,
+        },
+        min_platform_tools_rev: None,
+        archives: Vec::new(),
+        uses_license: None,
+        description: None,
+    };
+
+    loop {
+        match stream.next() {
+            Some(Event::StartElement { local_name, attributes }) => {
+                if local_name == "revision" {
+                    tool.revision = parse_revision(&mut stream, local_name)?;
+                } else if local_name == "min-platform-tools-rev" {
+                    tool.min_platform_tools_rev = Some(parse_revision(&mut stream, local_name)?);
+                } else if local_name == "uses-license" {
+                    tool.uses_license = Some(convert_uses_license(attributes)?);
+                } else if local_name == "description" {
+                    tool.description = Some(parse_string(&mut stream, local_name)?);
+                } else if local_name == "archives" {
+                    tool.archives = parse_archives(&mut stream)?;
+                }
+            }
+            Some(Event::EndElement { local_name }) => {
+                if local_name == "tool" {
+                    return Ok(tool);
+                }
+            }
+            Some(_) => {}
+            None => { return Err("parse error during tool".to_string()); }
+        }
+    }
+}
+
+/// Writes `sdk_repository` as a repository-11.xml manifest.
+///
+/// The output is a reduced manifest: only what `parse_sdk_repository` reads is written, so
+/// elements it skips (add-ons, samples, docs, `obsolete` flags, ...) are dropped. Licenses are
+/// written with `type="text"` and archives with their SHA-1 checksum alone, the only type the
+/// schema allows for either. Writing an archive without a SHA-1 checksum fails.
+pub fn write_sdk_repository<W: Write>(sdk_repository: &SdkRepository, sink: W) -> WriteResult<()> {
+    let mut writer = xmlhelper::create_writer(sink);
+
+    writer.write(XmlEvent::start_element("sdk:sdk-repository").ns("sdk", XML_NAMESPACE))?;
+    let mut license_ids: Vec<&String> = sdk_repository.licenses.keys().collect();
+    license_ids.sort();
+    for id in license_ids {
+        writer.write(XmlEvent::start_element("sdk:license").attr("id", id).attr("type", "text"))?;
+        writer.write(XmlEvent::characters(&sdk_repository.licenses[id]))?;
+        writer.write(XmlEvent::end_element())?;
+    }
+    for platform in &sdk_repository.platforms {
+        write_platform(&mut writer, platform)?;
+    }
+    for source in &sdk_repository.sources {
+        write_source(&mut writer, source)?;
+    }
+    for build_tool in &sdk_repository.build_tools {
+        write_build_tool(&mut writer, build_tool)?;
+    }
+    for platform_tool in &sdk_repository.platform_tools {
+        write_platform_tool(&mut writer, platform_tool)?;
+    }
+    for tool in &sdk_repository.tools {
+        write_tool(&mut writer, tool)?;
+    }
+    for ndk in &sdk_repository.ndks {
+        write_ndk(&mut writer, ndk)?;
+    }
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_ndk<W: Write>(writer: &mut EventWriter<W>, ndk: &Ndk) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:ndk"))?;
+    xmlhelper::write_text_element(writer, "sdk:revision", &ndk.revision.to_string())?;
+    write_uses_license(writer, &ndk.uses_license)?;
+    write_description(writer, &ndk.description)?;
+    write_archives(writer, &ndk.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_platform<W: Write>(writer: &mut EventWriter<W>, platform: &Platform) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:platform"))?;
+    xmlhelper::write_text_element(writer, "sdk:version", &platform.version)?;
+    xmlhelper::write_text_element(writer, "sdk:api-level", &platform.api_level.to_string())?;
+    if let Some(ref codename) = platform.codename {
+        xmlhelper::write_text_element(writer, "sdk:codename", codename)?;
+    }
+    xmlhelper::write_text_element(writer, "sdk:revision", &platform.revision.to_string())?;
+    if let Some(api) = platform.layoutlib_api {
+        writer.write(XmlEvent::start_element("sdk:layoutlib"))?;
+        xmlhelper::write_text_element(writer, "sdk:api", &api.to_string())?;
+        writer.write(XmlEvent::end_element())?;
+    }
+    write_uses_license(writer, &platform.uses_license)?;
+    if let Some(ref min_tools_rev) = platform.min_tools_rev {
+        write_revision(writer, "sdk:min-tools-rev", min_tools_rev)?;
+    }
+    write_description(writer, &platform.description)?;
+    write_archives(writer, &platform.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_source<W: Write>(writer: &mut EventWriter<W>, source: &Source) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:source"))?;
+    xmlhelper::write_text_element(writer, "sdk:api-level", &source.api_level.to_string())?;
+    xmlhelper::write_text_element(writer, "sdk:revision", &source.revision.to_string())?;
+    write_uses_license(writer, &source.uses_license)?;
+    write_description(writer, &source.description)?;
+    write_archives(writer, &source.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_build_tool<W: Write>(writer: &mut EventWriter<W>, build_tool: &BuildTool) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:build-tool"))?;
+    write_revision(writer, "sdk:revision", &build_tool.revision)?;
+    write_uses_license(writer, &build_tool.uses_license)?;
+    write_description(writer, &build_tool.description)?;
+    write_archives(writer, &build_tool.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_platform_tool<W: Write>(writer: &mut EventWriter<W>, platform_tool: &PlatformTool) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:platform-tool"))?;
+    write_revision(writer, "sdk:revision", &platform_tool.revision)?;
+    write_uses_license(writer, &platform_tool.uses_license)?;
+    write_description(writer, &platform_tool.description)?;
+    write_archives(writer, &platform_tool.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_tool<W: Write>(writer: &mut EventWriter<W>, tool: &Tool) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:tool"))?;
+    write_revision(writer, "sdk:revision", &tool.revision)?;
+    write_uses_license(writer, &tool.uses_license)?;
+    if let Some(ref min_platform_tools_rev) = tool.min_platform_tools_rev {
+        write_revision(writer, "sdk:min-platform-tools-rev", min_platform_tools_rev)?;
+    }
+    write_description(writer, &tool.description)?;
+    write_archives(writer, &tool.archives)?;
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_uses_license<W: Write>(writer: &mut EventWriter<W>, uses_license: &Option<String>) -> WriteResult<()> {
+    if let Some(ref r) = *uses_license {
+        writer.write(XmlEvent::start_element("sdk:uses-license").attr("ref", r))?;
+        writer.write(XmlEvent::end_element())?;
+    }
+    return Ok(());
+}
+
+fn write_description<W: Write>(writer: &mut EventWriter<W>, description: &Option<String>) -> WriteResult<()> {
+    if let Some(ref d) = *description {
+        xmlhelper::write_text_element(writer, "sdk:description", d)?;
+    }
+    return Ok(());
+}
+
+fn write_revision<W: Write>(writer: &mut EventWriter<W>, name: &str, revision: &Revision) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element(name))?;
+    xmlhelper::write_text_element(writer, "sdk:major", &revision.major.to_string())?;
+    if let Some(minor) = revision.minor {
+        xmlhelper::write_text_element(writer, "sdk:minor", &minor.to_string())?;
+    }
+    if let Some(micro) = revision.micro {
+        xmlhelper::write_text_element(writer, "sdk:micro", &micro.to_string())?;
+    }
+    if let Some(preview) = revision.preview {
+        xmlhelper::write_text_element(writer, "sdk:preview", &preview.to_string())?;
+    }
+    return writer.write(XmlEvent::end_element());
+}
+
+fn write_archives<W: Write>(writer: &mut EventWriter<W>, archives: &Vec<Archive>) -> WriteResult<()> {
+    writer.write(XmlEvent::start_element("sdk:archives"))?;
+    for archive in archives {
+        writer.write(XmlEvent::start_element("sdk:archive"))?;
+        xmlhelper::write_text_element(writer, "sdk:size", &archive.size.to_string())?;
+        // repository-11 allows a single checksum per archive, and only of type sha1.
+        let sha1 = match archive.checksums.iter().find(|c| c.algorithm == Algorithm::Sha1) {
+            Some(checksum) => checksum,
+            None => {
+                return Err(WriteError::Io(io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no SHA-1 checksum", archive.url))));
+            }
+        };
+        writer.write(XmlEvent::start_element("sdk:checksum").attr("type", sha1.algorithm.as_str()))?;
+        writer.write(XmlEvent::characters(&sha1.digest))?;
+        writer.write(XmlEvent::end_element())?;
+        xmlhelper::write_text_element(writer, "sdk:url", &archive.url)?;
+        if let Some(host_os) = archive.host_os {
+            xmlhelper::write_text_element(writer, "sdk:host-os", host_os.as_str())?;
+        }
+        if let Some(host_bits) = archive.host_bits {
+            xmlhelper::write_text_element(writer, "sdk:host-bits", host_bits.as_str())?;
+        }
+        writer.write(XmlEvent::end_element())?;
+    }
+    return writer.write(XmlEvent::end_element());
+}
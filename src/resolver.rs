@@ -0,0 +1,311 @@
+use local::LocalSdk;
+use package::{Dependency, Package};
+use package_id::PackageId;
+use repository11::{Revision, SdkRepository};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The package is not installed yet.
+    Install,
+    /// An older revision of the package is installed.
+    Upgrade,
+    /// A newer revision of the package is installed than the one requested.
+    Downgrade,
+    /// The installed revision already satisfies every requirement.
+    Keep,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Action::Install => "install",
+            Action::Upgrade => "upgrade",
+            Action::Downgrade => "downgrade",
+            Action::Keep => "keep",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Step<'a> {
+    pub package: &'a dyn Package,
+    pub action: Action,
+    pub installed_revision: Option<Revision>,
+    /// Packages depending on this one. Empty when the package was requested explicitly.
+    pub required_by: Vec<PackageId>,
+}
+
+/// Packages to install, ordered so that every package comes after its dependencies.
+#[derive(Debug)]
+pub struct Plan<'a> {
+    pub steps: Vec<Step<'a>>,
+}
+
+impl<'a> Plan<'a> {
+    /// Returns steps which actually download something.
+    pub fn pending(&self) -> Vec<&Step<'a>> {
+        return self.steps.iter().filter(|s| s.action != Action::Keep).collect();
+    }
+}
+
+/// A package to install, optionally pinned to a revision. Packages which are not pinned are
+/// brought to the newest revision available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub id: PackageId,
+    pub revision: Option<Revision>,
+}
+
+impl From<PackageId> for Request {
+    fn from(id: PackageId) -> Request {
+        Request {
+            id: id,
+            revision: None,
+        }
+    }
+}
+
+struct Requirement<'a> {
+    /// Revisions of the package available in the repository, newest first.
+    candidates: Vec<&'a dyn Package>,
+    package: &'a dyn Package,
+    requested: bool,
+    /// Revision the package was requested at.
+    revision: Option<Revision>,
+    /// Packages depending on this one, each with the minimum revision it requires.
+    required: Vec<(PackageId, Option<Revision>)>,
+    dependencies: Vec<PackageId>,
+}
+
+impl<'a> Requirement<'a> {
+    fn min_revision(&self) -> Option<Revision> {
+        return self.required.iter().filter_map(|r| r.1.clone()).max();
+    }
+
+    fn required_by(&self) -> Vec<PackageId> {
+        return self.required.iter().map(|r| r.0.clone()).collect();
+    }
+}
+
+/// Computes the dependency closure of `requested` packages, choosing for each of them the
+/// newest revision available in the repository which satisfies every requirement on it, or
+/// the revision it is pinned to.
+pub fn resolve<'a>(sdk_repository: &'a SdkRepository, local_sdk: &LocalSdk, requested: &[Request]) -> Result<Plan<'a>, String> {
+    let mut requirements: HashMap<PackageId, Requirement<'a>> = HashMap::new();
+    // Each dependency comes with the package and revision requiring it, if any.
+    let mut queue: VecDeque<(Dependency, Option<(PackageId, Revision)>)> = VecDeque::new();
+    let mut pins = HashMap::new();
+    for request in requested {
+        queue.push_back((Dependency { id: request.id.clone(), min_revision: None }, None));
+        if let Some(ref revision) = request.revision {
+            pins.insert(request.id.clone(), revision.clone());
+        }
+    }
+
+    while let Some((dependency, dependent)) = queue.pop_front() {
+        // Dependencies of a revision which has been replaced since no longer apply.
+        if let Some((ref dependent, ref revision)) = dependent {
+            if requirements.get(dependent).map(|r| r.package.revision() != *revision).unwrap_or(true) {
+                continue;
+            }
+        }
+        let dependent = dependent.map(|(id, _)| id);
+        let id = dependency.id.clone();
+
+        let replaced = match requirements.get_mut(&id) {
+            Some(requirement) => {
+                merge_requirement(requirement, dependency, dependent);
+                // A higher minimum revision may rule out the revision chosen so far.
+                match select(&requirement.candidates, requirement.min_revision().as_ref(), requirement.revision.as_ref()) {
+                    Some(package) if package.revision() != requirement.package.revision() => {
+                        requirement.package = package;
+                        Some(std::mem::take(&mut requirement.dependencies))
+                    }
+                    _ => None,
+                }
+            }
+            None => {
+                let candidates = sdk_repository.find_all(&id);
+                let pinned = pins.get(&id).cloned();
+                let package = match select(&candidates, dependency.min_revision.as_ref(), pinned.as_ref()).or(select(&candidates, None, pinned.as_ref())).or(candidates.first().cloned()) {
+                    Some(package) => package,
+                    None => {
+                        match dependent {
+                            None => { return Err(format!("package not found: {}", id)); }
+                            Some(dependent) => {
+                                // Dependencies missing from the repository are fine as long as they are installed.
+                                if satisfies(local_sdk, &dependency) {
+                                    continue;
+                                }
+                                return Err(format!("{} requires {} which is not available", dependent, format_dependency(&dependency)));
+                            }
+                        }
+                    }
+                };
+                let mut requirement = Requirement {
+                    candidates: candidates,
+                    package: package,
+                    requested: false,
+                    revision: pinned,
+                    required: Vec::new(),
+                    dependencies: Vec::new(),
+                };
+                merge_requirement(&mut requirement, dependency, dependent);
+                requirements.insert(id.clone(), requirement);
+                Some(Vec::new())
+            }
+        };
+
+        if let Some(replaced) = replaced {
+            drop_requirements(&mut requirements, &id, replaced);
+            let requirement = requirements.get_mut(&id).unwrap();
+            let mut dependencies = requirement.package.dependencies();
+            dependencies.extend(id.implied_dependencies());
+            for d in &dependencies {
+                queue.push_back((d.clone(), Some((id.clone(), requirement.package.revision()))));
+            }
+            requirement.dependencies = dependencies.into_iter().map(|d| d.id).collect();
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (id, requirement) in &requirements {
+        if let Some(ref revision) = requirement.revision {
+            if requirement.package.revision() != *revision {
+                conflicts.push(format!("{} {} is not available", id, revision));
+                continue;
+            }
+        }
+        if let Some(min_revision) = requirement.min_revision() {
+            if requirement.package.revision() < min_revision {
+                let available = match requirement.revision {
+                    Some(ref revision) => format!("{} is requested", revision),
+                    None => format!("the newest available revision is {}", requirement.candidates[0].revision()),
+                };
+                conflicts.push(format!("{} {} is required by {} but {}", id, min_revision, format_ids(&requirement.required_by()), available));
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        conflicts.sort();
+        return Err(format!("conflicting requirements:\n{}", conflicts.join("\n")));
+    }
+
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    let mut visiting = HashSet::new();
+    for request in requested {
+        visit(&request.id, &requirements, &mut visited, &mut visiting, &mut order)?;
+    }
+
+    let mut steps = Vec::new();
+    for id in order {
+        let requirement = requirements.remove(&id).unwrap();
+        let installed_revision = local_sdk.installed(&id).and_then(|p| p.revision);
+        let action = match installed_revision {
+            None => Action::Install,
+            Some(ref installed) if requirement.revision.is_some() && *installed > requirement.package.revision() => Action::Downgrade,
+            Some(ref installed) => {
+                // Explicitly requested packages are brought up to date, dependencies only as far as required.
+                let wanted = if requirement.requested {
+                    Some(requirement.package.revision())
+                } else {
+                    requirement.min_revision()
+                };
+                if wanted.map(|w| *installed < w).unwrap_or(false) {
+                    Action::Upgrade
+                } else {
+                    Action::Keep
+                }
+            }
+        };
+        if action != Action::Keep && requirement.package.archive_for_host().is_none() {
+            return Err(format!("{} has no archive for this host", id));
+        }
+        steps.push(Step {
+            package: requirement.package,
+            action: action,
+            installed_revision: installed_revision,
+            required_by: requirement.required_by(),
+        });
+    }
+    return Ok(Plan { steps: steps });
+}
+
+/// Picks the newest of `candidates` satisfying `min_revision` and `revision`, preferring
+/// revisions which can be installed on the running host.
+fn select<'a>(candidates: &[&'a dyn Package], min_revision: Option<&Revision>, revision: Option<&Revision>) -> Option<&'a dyn Package> {
+    let satisfying: Vec<&'a dyn Package> = candidates.iter().cloned().filter(|p| min_revision.map(|m| p.revision() >= *m).unwrap_or(true) && revision.map(|r| p.revision() == *r).unwrap_or(true)).collect();
+    return satisfying.iter().cloned().find(|p| p.archive_for_host().is_some()).or(satisfying.first().cloned());
+}
+
+fn merge_requirement(requirement: &mut Requirement, dependency: Dependency, dependent: Option<PackageId>) {
+    match dependent {
+        Some(dependent) => { requirement.required.push((dependent, dependency.min_revision)); }
+        None => { requirement.requested = true; }
+    }
+}
+
+/// Drops what `dependent` required of each of `ids`, along with the packages nothing else
+/// requires any more.
+fn drop_requirements(requirements: &mut HashMap<PackageId, Requirement>, dependent: &PackageId, ids: Vec<PackageId>) {
+    let mut stale: Vec<(PackageId, PackageId)> = ids.into_iter().map(|id| (dependent.clone(), id)).collect();
+    while let Some((dependent, id)) = stale.pop() {
+        let unused = match requirements.get_mut(&id) {
+            Some(requirement) => {
+                requirement.required.retain(|r| r.0 != dependent);
+                !requirement.requested && requirement.required.is_empty()
+            }
+            None => false,
+        };
+        if unused {
+            let requirement = requirements.remove(&id).unwrap();
+            stale.extend(requirement.dependencies.into_iter().map(|d| (id.clone(), d)));
+        }
+    }
+}
+
+fn satisfies(local_sdk: &LocalSdk, dependency: &Dependency) -> bool {
+    match local_sdk.installed(&dependency.id) {
+        Some(installed) => {
+            match (installed.revision, dependency.min_revision.as_ref()) {
+                (_, None) => true,
+                (Some(ref revision), Some(min_revision)) => revision >= min_revision,
+                (None, Some(_)) => false,
+            }
+        }
+        None => false,
+    }
+}
+
+fn visit(id: &PackageId, requirements: &HashMap<PackageId, Requirement>, visited: &mut HashSet<PackageId>, visiting: &mut HashSet<PackageId>, order: &mut Vec<PackageId>) -> Result<(), String> {
+    if visited.contains(id) {
+        return Ok(());
+    }
+    if !visiting.insert(id.clone()) {
+        return Err(format!("circular dependency involving {}", id));
+    }
+    // Dependencies satisfied by the local SDK alone have no requirement entry.
+    if let Some(requirement) = requirements.get(id) {
+        for dependency in &requirement.dependencies {
+            visit(dependency, requirements, visited, visiting, order)?;
+        }
+        order.push(id.clone());
+    }
+    visiting.remove(id);
+    visited.insert(id.clone());
+    return Ok(());
+}
+
+fn format_dependency(dependency: &Dependency) -> String {
+    match dependency.min_revision {
+        Some(ref min_revision) => format!("{} >= {}", dependency.id, min_revision),
+        None => dependency.id.to_string(),
+    }
+}
+
+fn format_ids(ids: &[PackageId]) -> String {
+    return ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ");
+}
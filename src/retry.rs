@@ -0,0 +1,119 @@
+use error::Error;
+use std::cmp;
+use std::thread;
+use std::time::Duration;
+
+/// How often and how long to wait before retrying an operation which failed with a retryable
+/// error (see `Error::is_retryable`). The delay doubles after every attempt up to `max_delay`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Attempts once.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_delay: Duration::from_secs(0),
+            max_delay: Duration::from_secs(0),
+        }
+    }
+
+    /// The delay after the `attempt`th attempt failed, starting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        let delay = self.initial_delay.checked_mul(factor).unwrap_or(self.max_delay);
+        return cmp::min(delay, self.max_delay);
+    }
+
+    /// Runs `f` until it succeeds, fails with an error which is not retryable, or
+    /// `max_attempts` is reached. Errors after more than one attempt carry the attempt count.
+    pub fn run<T, F: FnMut() -> Result<T, Error>>(&self, mut f: F) -> Result<T, Error> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Ok(value) => { return Ok(value); }
+                Err(e) => {
+                    if !e.is_retryable() || attempt >= self.max_attempts {
+                        if attempt > 1 {
+                            return Err(Error::Attempts(attempt, Box::new(e)));
+                        }
+                        return Err(e);
+                    }
+                    thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use error::Error;
+    use hyper::status::StatusCode;
+    use std::time::Duration;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts,
+            initial_delay: Duration::from_secs(0),
+            max_delay: Duration::from_secs(0),
+        }
+    }
+
+    fn failure<T>(status: StatusCode) -> Result<T, Error> {
+        return Err(Error::Status { url: "https://repo.example.com/android/tools_r25.zip".to_string(), status: status });
+    }
+
+    #[test]
+    fn double_delays_up_to_maximum() {
+        let delays: Vec<u64> = (1..8).map(|attempt| RetryPolicy::default().delay(attempt).as_secs()).collect();
+        assert_eq!(vec![1, 2, 4, 8, 16, 30, 30], delays);
+        assert_eq!(Duration::from_secs(30), RetryPolicy::default().delay(100));
+    }
+
+    #[test]
+    fn retry_until_success() {
+        let mut attempts = 0;
+        let result = policy(4).run(|| {
+            attempts += 1;
+            if attempts < 3 { failure(StatusCode::ServiceUnavailable) } else { Ok(attempts) }
+        });
+        assert_eq!(3, result.unwrap());
+    }
+
+    #[test]
+    fn report_attempts_of_last_error() {
+        let mut attempts = 0;
+        match policy(4).run(|| { attempts += 1; failure::<()>(StatusCode::GatewayTimeout) }) {
+            Err(Error::Attempts(4, _)) => {}
+            result => { panic!("unexpected result: {:?}", result); }
+        }
+        assert_eq!(4, attempts);
+    }
+
+    #[test]
+    fn do_not_retry_permanent_errors() {
+        let mut attempts = 0;
+        match policy(4).run(|| { attempts += 1; failure::<()>(StatusCode::NotFound) }) {
+            Err(Error::Status { status: StatusCode::NotFound, .. }) => {}
+            result => { panic!("unexpected result: {:?}", result); }
+        }
+        assert_eq!(1, attempts);
+    }
+}
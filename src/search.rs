@@ -0,0 +1,71 @@
+use package_id::PackageId;
+use repository11::SdkRepository;
+use std;
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub id: PackageId,
+    pub score: u32,
+}
+
+/// Scores how well `term` matches `candidate`, case-insensitively. Exact matches score highest,
+/// followed by prefix matches, substring matches and finally subsequence (fuzzy) matches.
+pub fn score(term: &str, candidate: &str) -> Option<u32> {
+    let term = term.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if term.is_empty() {
+        return None;
+    }
+    if candidate == term {
+        return Some(1000);
+    }
+    if candidate.starts_with(&term) {
+        return Some(800);
+    }
+    if let Some(i) = candidate.find(&term) {
+        return Some(600 - std::cmp::min(i as u32, 100));
+    }
+
+    // Every character of term must appear in order; each skipped character costs a point.
+    let mut gaps = 0;
+    let mut candidate_chars = candidate.chars();
+    for c in term.chars() {
+        loop {
+            match candidate_chars.next() {
+                Some(d) if d == c => { break; }
+                Some(_) => { gaps += 1; }
+                None => { return None; }
+            }
+        }
+    }
+    return Some(400 - std::cmp::min(gaps, 300));
+}
+
+fn best_score(term: &str, id: &PackageId, fields: &[Option<String>]) -> Option<u32> {
+    // Matches on the id are preferred over matches on descriptive fields.
+    let mut best = score(term, &id.to_string()).map(|s| s + 100);
+    for field in fields {
+        if let Some(ref field) = *field {
+            best = std::cmp::max(best, score(term, field));
+        }
+    }
+    return best;
+}
+
+/// Searches packages whose id, API level, version, codename or description matches `term`, best match first.
+pub fn search(sdk_repository: &SdkRepository, term: &str) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for package in sdk_repository.packages() {
+        let fields = [package.api_level().map(|a| a.to_string()), package.version().map(|v| v.to_string()), package.codename().map(|c| c.to_string()), Some(package.display_name()), package.description().map(|d| d.to_string())];
+        if let Some(score) = best_score(term, &package.id(), &fields) {
+            results.push(SearchResult { id: package.id(), score: score });
+        }
+    }
+    results.sort_by(|a, b| {
+        match b.score.cmp(&a.score) {
+            std::cmp::Ordering::Equal => a.id.to_string().cmp(&b.id.to_string()),
+            ordering => ordering,
+        }
+    });
+    return results;
+}
@@ -0,0 +1,210 @@
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use error::Error;
+use http;
+use hyper;
+use hyper::header::{ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec, Headers, Range};
+use hyper::status::StatusCode;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std;
+
+/// Sends GET requests for manifests and archives.
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Returns a response with status `Ok`, `PartialContent` or `NotModified`. Other statuses
+    /// are returned as `Error::Status`; in particular a range starting at or beyond the end of
+    /// the resource is `RangeNotSatisfiable`.
+    fn get(&self, request: &Request) -> Result<Response, Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    pub url: String,
+    /// Requests the body from this offset on. Transports may ignore it and send the whole body.
+    pub range_from: Option<u64>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<String>,
+}
+
+pub struct Response {
+    /// URL the response was received from, after following redirects.
+    pub url: String,
+    pub status: StatusCode,
+    pub content_length: Option<u64>,
+    /// Offset of the body within the resource, for `PartialContent` responses.
+    pub range_start: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Request {
+    pub fn new(url: &str) -> Request {
+        Request {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Sends requests with a `http::Client`.
+#[derive(Debug)]
+pub struct HttpTransport {
+    client: http::Client,
+}
+
+/// Reads `file://` URLs with `FileTransport` and sends other requests with `HttpTransport`.
+#[derive(Debug)]
+pub struct SchemeTransport {
+    http: HttpTransport,
+    file: FileTransport,
+}
+
+impl HttpTransport {
+    pub fn new(client: http::Client) -> HttpTransport {
+        HttpTransport {
+            client: client,
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, request: &Request) -> Result<Response, Error> {
+        let mut headers = Headers::new();
+        if let Some(offset) = request.range_from {
+            headers.set(Range::Bytes(vec![ByteRangeSpec::AllFrom(offset)]));
+        }
+        if let Some(ref etag) = request.if_none_match {
+            headers.set_raw("If-None-Match", vec![etag.clone().into_bytes()]);
+        }
+        if let Some(ref last_modified) = request.if_modified_since {
+            headers.set_raw("If-Modified-Since", vec![last_modified.clone().into_bytes()]);
+        }
+        let response = http::get(&self.client, &request.url, headers)?;
+        let range_start = match response.headers.get::<ContentRange>() {
+            Some(&ContentRange(ContentRangeSpec::Bytes { range: Some((start, _)), .. })) if response.status == StatusCode::PartialContent => Some(start),
+            _ => None,
+        };
+        return Ok(Response {
+            url: response.url.to_string(),
+            status: response.status,
+            content_length: response.headers.get::<ContentLength>().map(|&ContentLength(len)| len),
+            range_start: range_start,
+            etag: header_value(&response.headers, "ETag"),
+            last_modified: header_value(&response.headers, "Last-Modified"),
+            body: Box::new(response),
+        });
+    }
+}
+
+fn header_value(headers: &Headers, name: &str) -> Option<String> {
+    return headers.get_raw(name).and_then(|values| values.first()).and_then(|v| String::from_utf8(v.clone()).ok());
+}
+
+/// Reads `file://` URLs from the local filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileTransport;
+
+impl Transport for FileTransport {
+    fn get(&self, request: &Request) -> Result<Response, Error> {
+        let url = hyper::Url::parse(&request.url).map_err(|e| Error::Http(hyper::error::Error::Uri(e)))?;
+        let path = match url.to_file_path() {
+            Ok(ref path) if url.scheme() == "file" => path.clone(),
+            _ => { return Err(Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("not a file URL: {}", request.url)))); }
+        };
+        let mut file = File::open(&path).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let len = file.metadata()?.len();
+        let range_start = range_start(request, len)?;
+        if let Some(offset) = range_start {
+            file.seek(SeekFrom::Start(offset))?;
+        }
+        return Ok(Response {
+            url: request.url.clone(),
+            status: if range_start.is_some() { StatusCode::PartialContent } else { StatusCode::Ok },
+            content_length: Some(len - range_start.unwrap_or(0)),
+            range_start: range_start,
+            etag: None,
+            last_modified: None,
+            body: Box::new(file),
+        });
+    }
+}
+
+impl SchemeTransport {
+    pub fn new(client: http::Client) -> SchemeTransport {
+        SchemeTransport {
+            http: HttpTransport::new(client),
+            file: FileTransport,
+        }
+    }
+}
+
+impl Transport for SchemeTransport {
+    fn get(&self, request: &Request) -> Result<Response, Error> {
+        if request.url.starts_with("file:") {
+            return self.file.get(request);
+        }
+        return self.http.get(request);
+    }
+}
+
+/// Serves resources held in memory, keyed by URL. The SHA-1 of a resource is its ETag.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    resources: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> MemoryTransport {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, url: &str, body: Vec<u8>) {
+        self.resources.insert(url.to_string(), body);
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn get(&self, request: &Request) -> Result<Response, Error> {
+        let body = match self.resources.get(&request.url) {
+            Some(body) => body,
+            None => { return Err(Error::Status { url: request.url.clone(), status: StatusCode::NotFound }); }
+        };
+        let mut sha1 = Sha1::new();
+        sha1.input(body);
+        let etag = format!("\"{}\"", sha1.result_str());
+        if request.if_none_match.as_ref() == Some(&etag) {
+            return Ok(Response {
+                url: request.url.clone(),
+                status: StatusCode::NotModified,
+                content_length: Some(0),
+                range_start: None,
+                etag: Some(etag),
+                last_modified: None,
+                body: Box::new(std::io::empty()),
+            });
+        }
+        let len = body.len() as u64;
+        let range_start = range_start(request, len)?;
+        let offset = range_start.unwrap_or(0);
+        return Ok(Response {
+            url: request.url.clone(),
+            status: if range_start.is_some() { StatusCode::PartialContent } else { StatusCode::Ok },
+            content_length: Some(len - offset),
+            range_start: range_start,
+            etag: Some(etag),
+            last_modified: None,
+            body: Box::new(Cursor::new(body[offset as usize..].to_vec())),
+        });
+    }
+}
+
+fn range_start(request: &Request, len: u64) -> Result<Option<u64>, Error> {
+    match request.range_from {
+        Some(offset) if offset >= len => Err(Error::Status { url: request.url.clone(), status: StatusCode::RangeNotSatisfiable }),
+        Some(offset) => Ok(Some(offset)),
+        None => Ok(None),
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
-use std::io::Read;
+use std::io::{Read, Write};
 use xml::reader::{EventReader, Result, XmlEvent};
+use xml::writer;
+use xml::writer::EventWriter;
 
 #[derive(Debug)]
 pub enum Event {
@@ -13,7 +15,7 @@ pub fn parse<R: Read>(source: R) -> Result<Vec<Event>>{
     let reader = EventReader::new(source);
     let mut stream = Vec::new();
     for e in reader {
-        match try!(e) {
+        match e? {
             XmlEvent::StartElement { name, attributes, ..} => {
                 let mut attrs = HashMap::new();
                 for attr in attributes {
@@ -39,3 +41,13 @@ pub fn parse<R: Read>(source: R) -> Result<Vec<Event>>{
     }
     return Ok(stream);
 }
+
+pub fn create_writer<W: Write>(sink: W) -> EventWriter<W> {
+    return writer::EmitterConfig::new().perform_indent(true).indent_string("    ").create_writer(sink);
+}
+
+pub fn write_text_element<W: Write>(writer: &mut EventWriter<W>, name: &str, text: &str) -> writer::Result<()> {
+    writer.write(writer::XmlEvent::start_element(name))?;
+    writer.write(writer::XmlEvent::characters(text))?;
+    return writer.write(writer::XmlEvent::end_element());
+}
@@ -0,0 +1,79 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::Downloader;
+use android_sdk_cli::cache::EntryKind;
+use android_sdk_cli::checksum::{Algorithm, Checksum};
+use android_sdk_cli::retry::RetryPolicy;
+use common::{RecordingTransport, BASE_URL};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn download_into_cache() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("cache-download");
+    let downloader = Downloader::with_transport(transport.clone(), Some(cache.clone())).retry(RetryPolicy::never());
+    let archive = common::platform_tools();
+    let key = Checksum::strongest(&archive.checksums).unwrap();
+
+    let downloaded = downloader.fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+    assert_eq!(common::fixture("platform-tools_r23.zip"), common::read(downloaded.path()));
+    let path = cache.get(key).unwrap();
+    let old = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+    OpenOptions::new().write(true).open(&path).and_then(|f| f.set_modified(old)).unwrap();
+
+    downloader.fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+    assert_eq!(1, transport.requests().len());
+    // Reusing the archive keeps it from being cleaned up by age.
+    assert!(path.metadata().unwrap().modified().unwrap() > old);
+}
+
+#[test]
+fn list_entries() {
+    let (dir, cache) = common::temp_cache("cache-entries");
+    let sha1 = Checksum::new(Algorithm::Sha1, &"1".repeat(40));
+    let sha256 = Checksum::new(Algorithm::Sha256, &"2".repeat(64));
+    let partial = cache.partial_path(&sha256).unwrap();
+    for path in &[cache.path(&sha1), partial.clone(), partial.with_extension("part.lock"), dir.join("archives/3.zip"), dir.join("archives/README")] {
+        File::create(path).unwrap();
+    }
+
+    let entries: Vec<(String, EntryKind)> = cache.entries().unwrap().into_iter().map(|e| (e.checksum.digest, e.kind)).collect();
+
+    assert_eq!(vec![
+        ("1".repeat(40), EntryKind::Archive),
+        ("2".repeat(64), EntryKind::Partial),
+        ("2".repeat(64), EntryKind::Lock),
+    ], entries);
+}
+
+#[test]
+fn clean_partial_downloads() {
+    let (dir, cache) = common::temp_cache("cache-clean");
+    let now = SystemTime::now();
+    let old = now - Duration::from_secs(2 * 24 * 60 * 60);
+    let create = |path: &Path, modified: SystemTime| { File::create(path).and_then(|f| f.set_modified(modified)).unwrap(); };
+    let checksum = |i: u32| Checksum::new(Algorithm::Sha1, &i.to_string().repeat(40));
+    let lock_path = |checksum: &Checksum| cache.partial_path(checksum).unwrap().with_extension("part.lock");
+    // An old archive, whose lock was left behind.
+    create(&lock_path(&checksum(1)), old);
+    create(&cache.path(&checksum(1)), old);
+    // An old partial download.
+    create(&cache.partial_path(&checksum(2)).unwrap(), old);
+    create(&lock_path(&checksum(2)), old);
+    // A partial download written since, whose lock was created long ago.
+    create(&cache.partial_path(&checksum(3)).unwrap(), now);
+    create(&lock_path(&checksum(3)), old);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_android-sdk-cli")).args(["cache", "clean", "--older-than", "1d"]).env("ANDROID_SDK_CLI_CACHE_DIR", dir.path()).status().unwrap();
+
+    assert!(status.success());
+    let left: Vec<(String, EntryKind)> = cache.entries().unwrap().into_iter().map(|e| (e.checksum.digest, e.kind)).collect();
+    assert_eq!(vec![("3".repeat(40), EntryKind::Partial), ("3".repeat(40), EntryKind::Lock)], left);
+}
@@ -0,0 +1,217 @@
+#![allow(dead_code)]
+
+use android_sdk_cli::cache::Cache;
+use android_sdk_cli::checksum::{Algorithm, Checksum};
+use android_sdk_cli::error::Error;
+use android_sdk_cli::package_id::PackageId;
+use android_sdk_cli::repository11::{self, Archive, OsType, Platform, PlatformTool, Revision, SdkRepository, Tool};
+use android_sdk_cli::transport::{MemoryTransport, Request, Response, Transport};
+use android_sdk_cli::xmlhelper;
+use hyper::status::StatusCode;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std;
+
+pub static BASE_URL: &'static str = "https://repo.example.com/android";
+pub static MIRROR_URL: &'static str = "https://mirror.example.com/android";
+
+/// A transport recording every request it forwards along with the status of the response.
+#[derive(Debug)]
+pub struct RecordingTransport<T: Transport> {
+    inner: T,
+    requests: Mutex<Vec<(Request, StatusCode)>>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new(inner: T) -> RecordingTransport<T> {
+        RecordingTransport {
+            inner: inner,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn requests(&self) -> Vec<(Request, StatusCode)> {
+        return self.requests.lock().unwrap().clone();
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn get(&self, request: &Request) -> Result<Response, Error> {
+        let result = self.inner.get(request);
+        let status = match result {
+            Ok(ref response) => response.status,
+            Err(Error::Status { status, .. }) => status,
+            Err(_) => StatusCode::InternalServerError,
+        };
+        self.requests.lock().unwrap().push((request.clone(), status));
+        return result;
+    }
+}
+
+/// Directory of the fixture repository, holding `repository-11.xml` and its archives.
+pub fn fixture_dir() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/repository");
+}
+
+pub fn fixture(name: &str) -> Vec<u8> {
+    let mut content = Vec::new();
+    File::open(fixture_dir().join(name)).and_then(|mut f| f.read_to_end(&mut content)).unwrap();
+    return content;
+}
+
+/// The fixture repository with archive URLs resolved against `BASE_URL`.
+pub fn repository() -> SdkRepository {
+    return repository_at(BASE_URL);
+}
+
+pub fn repository_at(base_url: &str) -> SdkRepository {
+    let events = xmlhelper::parse(&fixture(repository11::XML_FILE)[..]).unwrap();
+    let mut sdk_repository = repository11::parse_sdk_repository(&mut events.into_iter()).unwrap();
+    sdk_repository.resolve_urls(base_url);
+    return sdk_repository;
+}
+
+/// Serves the fixture repository under `base_url`.
+pub fn serve(transport: &mut MemoryTransport, base_url: &str) {
+    for name in &[repository11::XML_FILE, "platform-tools_r23.zip", "tools_r25.zip"] {
+        transport.insert(&format!("{}/{}", base_url, name), fixture(name));
+    }
+}
+
+/// The fixture archive of platform-tools, under `BASE_URL`.
+pub fn platform_tools() -> Archive {
+    return repository().platform_tools[0].archives[0].clone();
+}
+
+/// A transport serving the fixture repository under `base_url`.
+pub fn serving(base_url: &str) -> MemoryTransport {
+    let mut transport = MemoryTransport::new();
+    serve(&mut transport, base_url);
+    return transport;
+}
+
+pub fn read(path: &Path) -> Vec<u8> {
+    let mut content = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut content)).unwrap();
+    return content;
+}
+
+/// A directory removed along with its content when dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        return &self.path;
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Creates an empty directory unique to `name` and this process.
+pub fn temp_dir(name: &str) -> TempDir {
+    let path = std::env::temp_dir().join(format!("android-sdk-cli-test-{}-{}", name, std::process::id()));
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+    return TempDir { path: path };
+}
+
+/// A cache in a directory of its own, removed along with the returned `TempDir`.
+pub fn temp_cache(name: &str) -> (TempDir, Cache) {
+    let dir = temp_dir(name);
+    let cache = Cache::new(dir.path());
+    return (dir, cache);
+}
+
+pub fn revision(s: &str) -> Revision {
+    return s.parse().unwrap();
+}
+
+/// An archive for the running host, or for another one.
+pub fn archive(for_host: bool) -> Archive {
+    let other = if OsType::current() == OsType::Windows { OsType::Linux } else { OsType::Windows };
+    Archive {
+        size: 1,
+        checksums: vec![Checksum { algorithm: Algorithm::Sha1, digest: "0".repeat(40) }],
+        url: "archive.zip".to_string(),
+        host_os: if for_host { None } else { Some(other) },
+        host_bits: None,
+    }
+}
+
+pub fn tool(rev: &str, min_platform_tools_rev: Option<&str>, for_host: bool) -> Tool {
+    Tool {
+        revision: revision(rev),
+        min_platform_tools_rev: min_platform_tools_rev.map(revision),
+        uses_license: None,
+        description: None,
+        archives: vec![archive(for_host)],
+    }
+}
+
+pub fn platform_tool(rev: &str) -> PlatformTool {
+    PlatformTool {
+        revision: revision(rev),
+        uses_license: None,
+        description: None,
+        archives: vec![archive(true)],
+    }
+}
+
+pub fn platform(api_level: u32, min_tools_rev: Option<&str>) -> Platform {
+    Platform {
+        version: String::new(),
+        api_level: api_level,
+        codename: None,
+        revision: 1,
+        layoutlib_api: None,
+        min_tools_rev: min_tools_rev.map(revision),
+        description: None,
+        archives: vec![archive(true)],
+        uses_license: None,
+    }
+}
+
+pub fn sdk_repository(platforms: Vec<Platform>, platform_tools: Vec<PlatformTool>, tools: Vec<Tool>) -> SdkRepository {
+    SdkRepository {
+        licenses: HashMap::new(),
+        ndks: Vec::new(),
+        platforms: platforms,
+        sources: Vec::new(),
+        build_tools: Vec::new(),
+        platform_tools: platform_tools,
+        tools: tools,
+    }
+}
+
+/// Installs `id` at `revision` into its install path under `root`.
+pub fn install(root: &Path, id: &str, revision: &str) {
+    install_at(root, id.parse::<PackageId>().unwrap().relative_path(), &format!("Pkg.Revision={}\n", revision));
+}
+
+/// Installs a package with `properties` into `path` under `root`.
+pub fn install_at<P: AsRef<Path>>(root: &Path, path: P, properties: &str) {
+    let dir = root.join(path);
+    std::fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("source.properties")).and_then(|mut f| f.write_all(properties.as_bytes())).unwrap();
+}
@@ -0,0 +1,173 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::Downloader;
+use android_sdk_cli::cache::Cache;
+use android_sdk_cli::checksum::Checksum;
+use android_sdk_cli::downloader::Mirrors;
+use android_sdk_cli::error::Error;
+use android_sdk_cli::lock::FileLock;
+use android_sdk_cli::repository11::Archive;
+use android_sdk_cli::retry::RetryPolicy;
+use android_sdk_cli::transport::MemoryTransport;
+use common::{RecordingTransport, BASE_URL, MIRROR_URL};
+use hyper::status::StatusCode;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn write_partial(cache: &Cache, archive: &Archive, content: &[u8]) {
+    let path = cache.partial_path(Checksum::strongest(&archive.checksums).unwrap()).unwrap();
+    File::create(path).and_then(|mut f| f.write_all(content)).unwrap();
+}
+
+fn downloader(transport: &Arc<RecordingTransport<MemoryTransport>>, cache: &Cache) -> Downloader {
+    return Downloader::with_transport(transport.clone(), Some(cache.clone())).retry(RetryPolicy::never());
+}
+
+#[test]
+fn resume_partial_download() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("downloader-resume");
+    let archive = common::platform_tools();
+    let content = common::fixture("platform-tools_r23.zip");
+    write_partial(&cache, &archive, &content[..100]);
+
+    let downloaded = downloader(&transport, &cache).fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+
+    assert_eq!(content, common::read(downloaded.path()));
+    let requests = transport.requests();
+    assert_eq!(1, requests.len());
+    assert_eq!(Some(100), requests[0].0.range_from);
+    assert_eq!(StatusCode::PartialContent, requests[0].1);
+}
+
+#[test]
+fn restart_when_range_not_satisfiable() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("downloader-restart");
+    let archive = common::platform_tools();
+    let content = common::fixture("platform-tools_r23.zip");
+    let mut partial = content.clone();
+    partial.extend_from_slice(b"garbage");
+    write_partial(&cache, &archive, &partial);
+
+    let downloaded = downloader(&transport, &cache).fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+
+    assert_eq!(content, common::read(downloaded.path()));
+    let requests = transport.requests();
+    assert_eq!(2, requests.len());
+    assert_eq!(StatusCode::RangeNotSatisfiable, requests[0].1);
+    assert_eq!(None, requests[1].0.range_from);
+    assert_eq!(StatusCode::Ok, requests[1].1);
+}
+
+#[test]
+fn restart_when_resumed_download_does_not_match() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("downloader-mismatched-prefix");
+    let archive = common::platform_tools();
+    let content = common::fixture("platform-tools_r23.zip");
+    write_partial(&cache, &archive, &[0; 100]);
+
+    let downloaded = downloader(&transport, &cache).fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+
+    assert_eq!(content, common::read(downloaded.path()));
+    let ranges: Vec<Option<u64>> = transport.requests().into_iter().map(|(request, _)| request.range_from).collect();
+    assert_eq!(vec![Some(100), None], ranges);
+}
+
+#[test]
+fn finish_complete_partial_download() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("downloader-complete");
+    let archive = common::platform_tools();
+    let content = common::fixture("platform-tools_r23.zip");
+    write_partial(&cache, &archive, &content);
+
+    let downloaded = downloader(&transport, &cache).fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+
+    assert_eq!(content, common::read(downloaded.path()));
+    let requests = transport.requests();
+    assert_eq!(1, requests.len());
+    assert_eq!(StatusCode::RangeNotSatisfiable, requests[0].1);
+}
+
+#[test]
+fn reject_checksum_mismatch() {
+    let archive = common::platform_tools();
+    let mut memory = MemoryTransport::new();
+    memory.insert(&archive.url, b"not the archive".to_vec());
+    let transport = Arc::new(RecordingTransport::new(memory));
+    let (_dir, cache) = common::temp_cache("downloader-mismatch");
+    let key = Checksum::strongest(&archive.checksums).unwrap().clone();
+
+    match downloader(&transport, &cache).fetch_archive(&archive.url, &archive.checksums, None) {
+        Err(Error::Checksum { url, expected, .. }) => {
+            assert_eq!(archive.url, url);
+            assert_eq!(key.to_string(), expected);
+        }
+        result => { panic!("unexpected result: {:?}", result); }
+    }
+    assert!(cache.get(&key).is_none());
+    assert!(!cache.partial_path(&key).unwrap().exists());
+}
+
+#[test]
+fn time_out_waiting_for_another_download() {
+    let transport = Arc::new(RecordingTransport::new(common::serving(BASE_URL)));
+    let (_dir, cache) = common::temp_cache("downloader-locked");
+    let archive = common::platform_tools();
+    let partial_path = cache.partial_path(Checksum::strongest(&archive.checksums).unwrap()).unwrap();
+    let _lock = FileLock::acquire(&partial_path.with_extension("part.lock"), Duration::from_secs(0), |_| {}).unwrap();
+
+    let error = downloader(&transport, &cache).lock_timeout(Duration::from_secs(0)).fetch_archive(&archive.url, &archive.checksums, None).unwrap_err();
+
+    assert!(error.to_string().contains(&format!("held by process {} ", std::process::id())), "{}", error);
+    assert!(transport.requests().is_empty());
+}
+
+#[test]
+fn fall_back_to_mirror() {
+    let archive = common::platform_tools();
+    let mut memory = common::serving(MIRROR_URL);
+    memory.insert(&archive.url, b"not the archive".to_vec());
+    let transport = Arc::new(RecordingTransport::new(memory));
+    let (_dir, cache) = common::temp_cache("downloader-mirror");
+    let mirrors = Mirrors {
+        base_url: BASE_URL.to_string(),
+        urls: vec!["https://missing.example.com/android".to_string(), MIRROR_URL.to_string()],
+    };
+
+    let downloaded = downloader(&transport, &cache).mirrors(mirrors).fetch_archive(&archive.url, &archive.checksums, None).unwrap();
+
+    assert_eq!(common::fixture("platform-tools_r23.zip"), common::read(downloaded.path()));
+    let requests: Vec<(String, StatusCode)> = transport.requests().into_iter().map(|(request, status)| (request.url, status)).collect();
+    assert_eq!(vec![
+        (archive.url.clone(), StatusCode::Ok),
+        ("https://missing.example.com/android/platform-tools_r23.zip".to_string(), StatusCode::NotFound),
+        (format!("{}/platform-tools_r23.zip", MIRROR_URL), StatusCode::Ok),
+    ], requests);
+}
+
+#[test]
+fn report_every_failed_mirror() {
+    let archive = common::platform_tools();
+    let transport = Arc::new(RecordingTransport::new(MemoryTransport::new()));
+    let (_dir, cache) = common::temp_cache("downloader-mirrors-failed");
+    let mirrors = Mirrors {
+        base_url: BASE_URL.to_string(),
+        urls: vec![MIRROR_URL.to_string()],
+    };
+
+    match downloader(&transport, &cache).mirrors(mirrors).fetch_archive(&archive.url, &archive.checksums, None) {
+        Err(Error::Mirrors(errors)) => {
+            let urls: Vec<String> = errors.into_iter().map(|(url, _)| url).collect();
+            assert_eq!(vec![archive.url.clone(), format!("{}/platform-tools_r23.zip", MIRROR_URL)], urls);
+        }
+        result => { panic!("unexpected result: {:?}", result); }
+    }
+}
@@ -0,0 +1,98 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::downloader::Mirrors;
+use android_sdk_cli::fetcher::Fetcher;
+use android_sdk_cli::retry::RetryPolicy;
+use android_sdk_cli::transport::MemoryTransport;
+use common::{RecordingTransport, BASE_URL, MIRROR_URL};
+use hyper::status::StatusCode;
+use std::time::Duration;
+
+fn manifest_url(base_url: &str) -> String {
+    return format!("{}/repository-11.xml", base_url);
+}
+
+#[test]
+fn revalidate_stale_manifest() {
+    let transport = RecordingTransport::new(common::serving(BASE_URL));
+    let (dir, cache) = common::temp_cache("fetcher-revalidate");
+    let fetcher = Fetcher::new(&transport, Some(cache)).max_age(Duration::from_secs(0));
+
+    fetcher.fetch(&manifest_url(BASE_URL)).unwrap();
+    let sdk_repository = fetcher.fetch(&manifest_url(BASE_URL)).unwrap();
+
+    let requests = transport.requests();
+    assert_eq!(2, requests.len());
+    assert_eq!(StatusCode::Ok, requests[0].1);
+    assert_eq!(None, requests[0].0.if_none_match);
+    assert!(requests[1].0.if_none_match.is_some());
+    assert_eq!(StatusCode::NotModified, requests[1].1);
+    assert_eq!(format!("{}/platform-tools_r23.zip", BASE_URL), sdk_repository.platform_tools[0].archives[0].url);
+    // The manifest and its metadata are written through temporary files renamed into place.
+    let mut names: Vec<String> = std::fs::read_dir(dir.join("manifests")).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    names.sort();
+    assert_eq!(vec!["https___repo.example.com_android_repository-11.xml.properties", "https___repo.example.com_android_repository-11.xml.xml"], names);
+}
+
+#[test]
+fn reuse_fresh_manifest() {
+    let transport = RecordingTransport::new(common::serving(BASE_URL));
+    let (_dir, cache) = common::temp_cache("fetcher-fresh");
+    let fetcher = Fetcher::new(&transport, Some(cache));
+
+    fetcher.fetch(&manifest_url(BASE_URL)).unwrap();
+    fetcher.fetch(&manifest_url(BASE_URL)).unwrap();
+
+    assert_eq!(1, transport.requests().len());
+}
+
+#[test]
+fn use_cached_manifest_offline() {
+    let transport = RecordingTransport::new(common::serving(BASE_URL));
+    let (_dir, cache) = common::temp_cache("fetcher-offline");
+    let offline = Fetcher::new(&transport, Some(cache.clone())).max_age(Duration::from_secs(0)).offline(true);
+
+    let error = offline.fetch(&manifest_url(BASE_URL)).unwrap_err();
+    assert!(error.starts_with("no cached manifest for "), "{}", error);
+
+    Fetcher::new(&transport, Some(cache)).fetch(&manifest_url(BASE_URL)).unwrap();
+    offline.fetch(&manifest_url(BASE_URL)).unwrap();
+
+    assert_eq!(1, transport.requests().len());
+}
+
+#[test]
+fn fall_back_to_mirror() {
+    let transport = RecordingTransport::new(common::serving(MIRROR_URL));
+    let mirrors = Mirrors {
+        base_url: BASE_URL.to_string(),
+        urls: vec![MIRROR_URL.to_string()],
+    };
+    let fetcher = Fetcher::new(&transport, None).retry(RetryPolicy::never()).mirrors(mirrors);
+
+    let sdk_repository = fetcher.fetch(&manifest_url(BASE_URL)).unwrap();
+
+    let urls: Vec<String> = transport.requests().into_iter().map(|(request, _)| request.url).collect();
+    assert_eq!(vec![manifest_url(BASE_URL), manifest_url(MIRROR_URL)], urls);
+    // Archives are still resolved against the repository and fall back to mirrors on their own.
+    assert_eq!(format!("{}/tools_r25.zip", BASE_URL), sdk_repository.tools[0].archives[0].url);
+}
+
+#[test]
+fn report_every_failed_mirror() {
+    let transport = MemoryTransport::new();
+    let mirrors = Mirrors {
+        base_url: BASE_URL.to_string(),
+        urls: vec![MIRROR_URL.to_string()],
+    };
+    let fetcher = Fetcher::new(&transport, None).retry(RetryPolicy::never()).mirrors(mirrors);
+
+    let error = fetcher.fetch(&manifest_url(BASE_URL)).unwrap_err();
+
+    assert!(error.starts_with("all mirrors failed:"));
+    assert!(error.contains(&manifest_url(BASE_URL)));
+    assert!(error.contains(&manifest_url(MIRROR_URL)));
+}
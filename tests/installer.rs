@@ -0,0 +1,113 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::{Downloader, Installer, Package, PackageId};
+use android_sdk_cli::installer;
+use android_sdk_cli::local::LocalSdk;
+use android_sdk_cli::repository11::Revision;
+use android_sdk_cli::retry::RetryPolicy;
+use android_sdk_cli::transport::{FileTransport, MemoryTransport};
+use common::BASE_URL;
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn installed_revision(local_sdk: &LocalSdk, id: &PackageId) -> Option<Revision> {
+    return local_sdk.installed(id).and_then(|p| p.revision);
+}
+
+#[test]
+fn install_all_from_files() {
+    let sdk_repository = common::repository_at(&format!("file://{}", common::fixture_dir().display()));
+    let root = common::temp_dir("installer-files");
+    let local_sdk = LocalSdk::new(root.path());
+    let downloader = Downloader::with_transport(Arc::new(FileTransport), None);
+    let installer = Installer::new(&downloader, &local_sdk);
+    let packages: Vec<&dyn Package> = vec![&sdk_repository.platform_tools[0], &sdk_repository.tools[0]];
+
+    let mut installed = Vec::new();
+    installer.install_all(&packages, 2, |package| installed.push(package.id())).unwrap();
+
+    assert_eq!(vec![PackageId::PlatformTools, PackageId::Tools], installed);
+    assert_eq!(Some(Revision::from_str("23").unwrap()), installed_revision(&local_sdk, &PackageId::PlatformTools));
+    assert_eq!(Some(Revision::from_str("25").unwrap()), installed_revision(&local_sdk, &PackageId::Tools));
+}
+
+#[test]
+fn install_packages_before_failed_download() {
+    let sdk_repository = common::repository();
+    let mut memory = common::serving(BASE_URL);
+    memory.insert(&format!("{}/tools_r25.zip", BASE_URL), b"not the archive".to_vec());
+    let root = common::temp_dir("installer-failed");
+    let local_sdk = LocalSdk::new(root.path());
+    let downloader = Downloader::with_transport(Arc::new(memory), None).retry(RetryPolicy::never());
+    let installer = Installer::new(&downloader, &local_sdk);
+    let packages: Vec<&dyn Package> = vec![&sdk_repository.platform_tools[0], &sdk_repository.tools[0]];
+
+    let mut installed = Vec::new();
+    let error = installer.install_all(&packages, 2, |package| installed.push(package.id())).unwrap_err();
+
+    assert!(error.starts_with("tools: "));
+    assert_eq!(vec![PackageId::PlatformTools], installed);
+    assert!(installed_revision(&local_sdk, &PackageId::PlatformTools).is_some());
+    assert!(installed_revision(&local_sdk, &PackageId::Tools).is_none());
+}
+
+#[test]
+fn skip_packages_after_failed_download() {
+    let sdk_repository = common::repository();
+    let mut memory = common::serving(BASE_URL);
+    memory.insert(&format!("{}/platform-tools_r23.zip", BASE_URL), b"not the archive".to_vec());
+    let root = common::temp_dir("installer-skipped");
+    let local_sdk = LocalSdk::new(root.path());
+    let downloader = Downloader::with_transport(Arc::new(memory), None).retry(RetryPolicy::never());
+    let installer = Installer::new(&downloader, &local_sdk);
+    let packages: Vec<&dyn Package> = vec![&sdk_repository.platform_tools[0], &sdk_repository.tools[0]];
+
+    let mut installed = Vec::new();
+    let error = installer.install_all(&packages, 2, |package| installed.push(package.id())).unwrap_err();
+
+    assert!(error.starts_with("platform-tools: "));
+    assert!(installed.is_empty());
+    assert!(installed_revision(&local_sdk, &PackageId::Tools).is_none());
+}
+
+#[test]
+fn report_every_failed_download() {
+    let sdk_repository = common::repository();
+    let mut memory = MemoryTransport::new();
+    memory.insert(&format!("{}/tools_r25.zip", BASE_URL), b"not the archive".to_vec());
+    let root = common::temp_dir("installer-failures");
+    let local_sdk = LocalSdk::new(root.path());
+    let downloader = Downloader::with_transport(Arc::new(memory), None).retry(RetryPolicy::never());
+    let installer = Installer::new(&downloader, &local_sdk);
+    let packages: Vec<&dyn Package> = vec![&sdk_repository.platform_tools[0], &sdk_repository.tools[0]];
+
+    let error = installer.install_all(&packages, 2, |_| {}).unwrap_err();
+
+    assert!(error.starts_with("platform-tools: "), "{}", error);
+    assert!(error.contains("\ntools: "), "{}", error);
+}
+
+#[test]
+fn recover_interrupted_installations() {
+    let sdk_repository = common::repository_at(&format!("file://{}", common::fixture_dir().display()));
+    let root = common::temp_dir("installer-recover");
+    let local_sdk = LocalSdk::new(root.path());
+    let downloader = Downloader::with_transport(Arc::new(FileTransport), None);
+    let packages: Vec<&dyn Package> = vec![&sdk_repository.platform_tools[0], &sdk_repository.tools[0]];
+    Installer::new(&downloader, &local_sdk).install_all(&packages, 1, |_| {}).unwrap();
+    // tools was moved aside and platform-tools moved back in before the interruption.
+    std::fs::rename(root.join("tools"), root.join(".backup-tools")).unwrap();
+    std::fs::create_dir_all(root.join(".backup-platform-tools")).unwrap();
+    std::fs::create_dir_all(root.join(".staging-tools/tools")).unwrap();
+
+    installer::recover(&local_sdk).unwrap();
+
+    assert_eq!(Some(Revision::from_str("23").unwrap()), installed_revision(&local_sdk, &PackageId::PlatformTools));
+    assert_eq!(Some(Revision::from_str("25").unwrap()), installed_revision(&local_sdk, &PackageId::Tools));
+    let mut names: Vec<String> = std::fs::read_dir(root.path()).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    names.sort();
+    assert_eq!(vec!["platform-tools", "tools"], names);
+}
@@ -0,0 +1,39 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::local::{InstalledPackage, LocalSdk};
+use android_sdk_cli::package_id::PackageId;
+use common::install_at;
+
+fn find(local_sdk: &LocalSdk, id: &str) -> InstalledPackage {
+    return local_sdk.find(&id.parse::<PackageId>().unwrap()).unwrap();
+}
+
+#[test]
+fn removal_conflicts() {
+    let root = common::temp_dir("local-conflicts");
+    install_at(&root, "tools", "Pkg.Revision=25\nPlatform.MinPlatformToolsRev=20\n");
+    install_at(&root, "platform-tools", "Pkg.Revision=23\n");
+    install_at(&root, "platforms/android-23", "Pkg.Revision=2\nAndroidVersion.ApiLevel=23\n");
+    install_at(&root, "platforms/android-6.0", "Pkg.Revision=2\nAndroidVersion.ApiLevel=23\n");
+    install_at(&root, "system-images/android-23/google_apis/x86", "Pkg.Revision=5\n");
+    install_at(&root, "system-images/android-24/google_apis/x86", "Pkg.Revision=5\n");
+    install_at(&root, "platforms/android-24", "Pkg.Revision=1\nAndroidVersion.ApiLevel=24\n");
+    let local_sdk = LocalSdk::new(root.path());
+
+    let packages = vec![find(&local_sdk, "platform-tools"), find(&local_sdk, "tools")];
+    assert_eq!(vec!["platform-tools is required by tools"], local_sdk.removal_conflicts(&packages[..1]));
+    // Packages removed together do not hold each other back.
+    assert!(local_sdk.removal_conflicts(&packages).is_empty());
+
+    let platform = find(&local_sdk, "platforms;android-24");
+    assert_eq!(vec!["platforms;android-24 is required by system-images;android-24;google_apis;x86"], local_sdk.removal_conflicts(&[platform]));
+
+    // platforms/android-6.0 still provides API 23, unless it is removed too.
+    let providers = local_sdk.providers(&PackageId::Platform(23));
+    assert_eq!(2, providers.len());
+    assert!(local_sdk.removal_conflicts(&providers[..1]).is_empty());
+    assert_eq!(vec!["platforms;android-23 is required by system-images;android-23;google_apis;x86"], local_sdk.removal_conflicts(&providers));
+}
@@ -0,0 +1,163 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::checksum::{Algorithm, Checksum};
+use android_sdk_cli::local::LocalSdk;
+use android_sdk_cli::lockfile::{LockedPackage, Lockfile};
+use android_sdk_cli::package_id::PackageId;
+use android_sdk_cli::repository11::{Archive, BitSize, OsType, SdkRepository};
+use android_sdk_cli::resolver::{self, Action, Request};
+use common::{platform, platform_tool, revision, sdk_repository, tool, BASE_URL, MIRROR_URL};
+
+fn lockfile() -> Lockfile {
+    Lockfile {
+        manifest_digest: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string(),
+        packages: vec![
+            LockedPackage {
+                id: "tools".parse().unwrap(),
+                revision: revision("25.1.6"),
+                archives: vec![Archive {
+                    size: 284,
+                    checksums: vec![
+                        Checksum::new(Algorithm::Sha1, "baa966bdd1147515899829ed220be340f536883d"),
+                        Checksum::new(Algorithm::Sha256, "dcda84490e3a2029e9b46252a3e1650039ea23e037671891633071218ac4fdf5"),
+                    ],
+                    url: "tools_r25.1.6-linux.zip".to_string(),
+                    host_os: Some(OsType::Linux),
+                    host_bits: Some(BitSize::Bit64),
+                }],
+            },
+            LockedPackage {
+                id: "platforms;android-23".parse().unwrap(),
+                revision: revision("3"),
+                archives: vec![Archive {
+                    size: 70433421,
+                    checksums: vec![Checksum::new(Algorithm::Sha1, "f4f1e0ee23ba5fd2eb7ba7e3e8f3eb3b1ce37d6f")],
+                    url: "https://mirror.example.com/android-23_r03.zip".to_string(),
+                    host_os: None,
+                    host_bits: None,
+                }],
+            },
+        ],
+    }
+}
+
+/// A repository of tools 25 requiring platform-tools 23 and of platform 23 requiring tools 25,
+/// with archives under `base_url`.
+fn repository(base_url: &str) -> SdkRepository {
+    let mut sdk_repository = sdk_repository(vec![platform(23, Some("25"))], vec![platform_tool("23"), platform_tool("24")], vec![tool("25", Some("23"), true)]);
+    sdk_repository.platforms[0].archives[0].url = "android-23_r01.zip".to_string();
+    sdk_repository.platform_tools[0].archives[0].url = "platform-tools_r23.zip".to_string();
+    sdk_repository.platform_tools[1].archives[0].url = "platform-tools_r24.zip".to_string();
+    sdk_repository.tools[0].archives[0].url = "tools_r25.zip".to_string();
+    sdk_repository.resolve_urls(base_url);
+    return sdk_repository;
+}
+
+fn lock(sdk_repository: &SdkRepository, local_sdk: &LocalSdk) -> Lockfile {
+    let plan = resolver::resolve(sdk_repository, local_sdk, &[Request::from(PackageId::Platform(23))]).unwrap();
+    return Lockfile::from_plan(&plan, sdk_repository, BASE_URL, "digest");
+}
+
+fn locked(lockfile: &Lockfile) -> Vec<String> {
+    return lockfile.packages.iter().map(|p| format!("{} {} {}", p.id, p.revision, p.archives.iter().map(|a| a.url.clone()).collect::<Vec<String>>().join(" "))).collect();
+}
+
+#[test]
+fn round_trip() {
+    let lockfile = lockfile();
+    let text = lockfile.to_string();
+
+    assert_eq!(lockfile, text.parse().unwrap());
+
+    let dir = common::temp_dir("lockfile-round-trip");
+    let path = dir.join("android-sdk.lock");
+    lockfile.save(&path).unwrap();
+    assert_eq!(lockfile, Lockfile::load(&path).unwrap());
+    assert_eq!(vec!["android-sdk.lock"], std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect::<Vec<String>>());
+}
+
+#[test]
+fn reject_invalid_lockfile() {
+    for text in &["[[package]]\nrevision = \"1\"\n", "[[package]]\nid = \"tools\"\nrevision = \"1\"\n[[package.archive]]\nurl = \"a.zip\"\nsize = 1\n", "package = 1\nx = \n"] {
+        assert!(text.parse::<Lockfile>().is_err(), "{} was accepted", text);
+    }
+}
+
+#[test]
+fn diff() {
+    let locked = lockfile();
+    assert!(locked.diff(&locked).is_empty());
+
+    let mut other = lockfile();
+    other.packages[0].revision = revision("25.2.3");
+    other.packages[1].archives[0].size += 1;
+    other.packages.push(LockedPackage {
+        id: "platform-tools".parse().unwrap(),
+        revision: revision("24"),
+        archives: Vec::new(),
+    });
+    assert_eq!(vec![
+        "tools is locked at revision 25.1.6 but resolves to 25.2.3",
+        "platforms;android-23 3 has different archives than locked",
+        "platform-tools 24 is not locked",
+    ], locked.diff(&other));
+    assert_eq!(vec![
+        "tools is locked but no longer required",
+        "platforms;android-23 is locked but no longer required",
+    ], locked.diff(&Lockfile { manifest_digest: String::new(), packages: Vec::new() }));
+}
+
+#[test]
+fn lock_in_installation_order() {
+    let sdk_repository = repository(BASE_URL);
+    let root = common::temp_dir("lockfile-order");
+    let local_sdk = LocalSdk::new(root.path());
+
+    let lockfile = lock(&sdk_repository, &local_sdk);
+
+    assert_eq!(vec!["platform-tools 24 platform-tools_r24.zip", "tools 25 tools_r25.zip", "platforms;android-23 1 android-23_r01.zip"], locked(&lockfile));
+    let plan = lockfile.plan(&local_sdk).unwrap();
+    let ids: Vec<String> = plan.steps.iter().map(|s| s.package.id().to_string()).collect();
+    assert_eq!(vec!["platform-tools", "tools", "platforms;android-23"], ids);
+}
+
+#[test]
+fn lock_kept_revisions_from_repository() {
+    let sdk_repository = repository(BASE_URL);
+    let root = common::temp_dir("lockfile-kept");
+    // Listed by the repository, so kept and locked as installed.
+    common::install(&root, "platform-tools", "23");
+    // Not listed, so locked at the planned revision instead.
+    common::install(&root, "tools", "25.0.1");
+    let local_sdk = LocalSdk::new(root.path());
+
+    let lockfile = lock(&sdk_repository, &local_sdk);
+
+    assert_eq!(vec!["platform-tools 23 platform-tools_r23.zip", "tools 25 tools_r25.zip", "platforms;android-23 1 android-23_r01.zip"], locked(&lockfile));
+    let actions: Vec<Action> = lockfile.plan(&local_sdk).unwrap().steps.iter().map(|s| s.action).collect();
+    assert_eq!(vec![Action::Keep, Action::Downgrade, Action::Install], actions);
+    // A fresh machine installs every locked package.
+    let fresh_root = common::temp_dir("lockfile-kept-fresh");
+    let fresh = LocalSdk::new(fresh_root.path());
+    assert!(lockfile.plan(&fresh).unwrap().steps.iter().all(|s| s.action == Action::Install && s.package.archive_for_host().is_some()));
+}
+
+#[test]
+fn verify_against_repository() {
+    let mut sdk_repository = repository(BASE_URL);
+    let root = common::temp_dir("lockfile-verify");
+    let lockfile = lock(&sdk_repository, &LocalSdk::new(root.path()));
+    assert!(lockfile.verify(&sdk_repository, BASE_URL).is_empty());
+    // Relative URLs still match the repository served from a mirror.
+    assert!(lockfile.verify(&repository(MIRROR_URL), MIRROR_URL).is_empty());
+
+    sdk_repository.platform_tools.remove(1);
+    sdk_repository.tools[0].archives[0].checksums[0] = Checksum::new(Algorithm::Sha1, &"1".repeat(40));
+    assert_eq!(vec![
+        "platform-tools 24 is no longer in the repository",
+        "tools 25 has different archives than locked",
+    ], lockfile.verify(&sdk_repository, BASE_URL));
+}
@@ -0,0 +1,89 @@
+extern crate android_sdk_cli;
+
+use android_sdk_cli::checksum::Algorithm;
+use android_sdk_cli::repository11::{self, SdkRepository};
+use android_sdk_cli::xmlhelper::{self, Event};
+use std::fs::File;
+
+fn parse(xml: &[u8]) -> SdkRepository {
+    let events = xmlhelper::parse(xml).unwrap();
+    return repository11::parse_sdk_repository(&mut events.into_iter()).unwrap();
+}
+
+fn fixture() -> SdkRepository {
+    let events = xmlhelper::parse(File::open("tests/fixtures/repository-11.xml").unwrap()).unwrap();
+    return repository11::parse_sdk_repository(&mut events.into_iter()).unwrap();
+}
+
+fn write(sdk_repository: &SdkRepository) -> Vec<u8> {
+    let mut xml = Vec::new();
+    repository11::write_sdk_repository(sdk_repository, &mut xml).unwrap();
+    return xml;
+}
+
+/// Checks the parts of the repository-11 schema the writer has a choice about: the namespace
+/// and a single SHA-1 checksum per archive.
+fn assert_valid(xml: &[u8]) {
+    assert!(String::from_utf8(xml.to_vec()).unwrap().contains("xmlns:sdk=\"http://schemas.android.com/sdk/android/repository/11\""));
+    let mut checksums = None;
+    let mut archives = 0;
+    for event in xmlhelper::parse(xml).unwrap() {
+        match event {
+            Event::StartElement { ref local_name, .. } if local_name == "archive" => {
+                checksums = Some(0);
+            }
+            Event::StartElement { ref local_name, ref attributes } if local_name == "checksum" => {
+                assert_eq!(Some("sha1"), attributes.get("type").map(|t| t.as_str()));
+                checksums = checksums.map(|n| n + 1);
+            }
+            Event::EndElement { ref local_name } if local_name == "archive" => {
+                assert_eq!(Some(1), checksums);
+                checksums = None;
+                archives += 1;
+            }
+            _ => {}
+        }
+    }
+    assert!(archives > 0);
+}
+
+#[test]
+fn round_trip() {
+    let mut original = fixture();
+    // Only the SHA-1 checksums survive, as repository-11 allows no other.
+    for build_tool in &mut original.build_tools {
+        for archive in &mut build_tool.archives {
+            archive.checksums.retain(|c| c.algorithm == Algorithm::Sha1);
+        }
+    }
+    let xml = write(&original);
+    let written = parse(&xml);
+
+    assert_eq!(original.licenses, written.licenses);
+    assert_eq!(format!("{:?}", original.platforms), format!("{:?}", written.platforms));
+    assert_eq!(format!("{:?}", original.sources), format!("{:?}", written.sources));
+    assert_eq!(format!("{:?}", original.build_tools), format!("{:?}", written.build_tools));
+    assert_eq!(format!("{:?}", original.platform_tools), format!("{:?}", written.platform_tools));
+    assert_eq!(format!("{:?}", original.tools), format!("{:?}", written.tools));
+    assert_eq!(format!("{:?}", original.ndks), format!("{:?}", written.ndks));
+    assert_eq!(xml, write(&written));
+}
+
+#[test]
+fn write_reduced_manifest() {
+    let xml = write(&fixture());
+    assert_valid(&xml);
+
+    let xml = String::from_utf8(xml).unwrap();
+    assert!(!xml.contains("sdk:sample"));
+    assert!(!xml.contains("samples-23_r02.zip"));
+    assert!(xml.contains("<sdk:license id=\"android-sdk-license\" type=\"text\">"));
+}
+
+#[test]
+fn refuse_archive_without_sha1() {
+    let mut sdk_repository = fixture();
+    sdk_repository.build_tools[0].archives[0].checksums.retain(|c| c.algorithm != Algorithm::Sha1);
+
+    assert!(repository11::write_sdk_repository(&sdk_repository, Vec::new()).is_err());
+}
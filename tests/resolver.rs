@@ -0,0 +1,128 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::local::LocalSdk;
+use android_sdk_cli::repository11::SdkRepository;
+use android_sdk_cli::resolver::{self, Request};
+use common::{platform, platform_tool, revision, sdk_repository, tool};
+
+fn request(id: &str, rev: Option<&str>) -> Request {
+    Request {
+        id: id.parse().unwrap(),
+        revision: rev.map(revision),
+    }
+}
+
+struct Case {
+    name: &'static str,
+    repository: SdkRepository,
+    /// Installed packages and their revisions.
+    installed: Vec<(&'static str, &'static str)>,
+    requests: Vec<Request>,
+    /// Steps as `<action> <id> <revision> [<- <required by>]`, or the start of the error.
+    expected: Result<Vec<&'static str>, &'static str>,
+}
+
+fn resolve(case: &Case) -> Result<Vec<String>, String> {
+    let root = common::temp_dir(&format!("resolver-{}", case.name));
+    for &(id, rev) in &case.installed {
+        common::install(&root, id, rev);
+    }
+    let local_sdk = LocalSdk::new(root.path());
+    let plan = resolver::resolve(&case.repository, &local_sdk, &case.requests)?;
+    return Ok(plan.steps.iter().map(|step| {
+        let mut s = format!("{} {} {}", step.action.as_str(), step.package.id(), step.package.revision());
+        if !step.required_by.is_empty() {
+            s.push_str(&format!(" <- {}", step.required_by.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ")));
+        }
+        s
+    }).collect());
+}
+
+#[test]
+fn resolve_requests() {
+    let cases = vec![
+        Case {
+            name: "closure",
+            repository: sdk_repository(vec![], vec![platform_tool("23"), platform_tool("24")], vec![tool("25", Some("23"), true)]),
+            installed: vec![],
+            requests: vec![request("tools", None)],
+            expected: Ok(vec!["install platform-tools 24 <- tools", "install tools 25"]),
+        },
+        Case {
+            name: "dependency-up-to-date",
+            repository: sdk_repository(vec![], vec![platform_tool("23"), platform_tool("24")], vec![tool("25", Some("23"), true)]),
+            installed: vec![("platform-tools", "23.1")],
+            requests: vec![request("tools", None)],
+            expected: Ok(vec!["keep platform-tools 24 <- tools", "install tools 25"]),
+        },
+        Case {
+            name: "dependency-too-old",
+            repository: sdk_repository(vec![], vec![platform_tool("23"), platform_tool("24")], vec![tool("25", Some("23"), true)]),
+            installed: vec![("platform-tools", "22")],
+            requests: vec![request("tools", None)],
+            expected: Ok(vec!["upgrade platform-tools 24 <- tools", "install tools 25"]),
+        },
+        Case {
+            name: "highest-minimum",
+            repository: sdk_repository(vec![platform(23, Some("22")), platform(24, Some("24.1"))], vec![], vec![tool("24", None, true), tool("25", None, true)]),
+            installed: vec![("tools", "24")],
+            requests: vec![request("platforms;android-23", None), request("platforms;android-24", None)],
+            expected: Ok(vec!["upgrade tools 25 <- platforms;android-23, platforms;android-24", "install platforms;android-23 1", "install platforms;android-24 1"]),
+        },
+        Case {
+            name: "pin",
+            repository: sdk_repository(vec![platform(23, Some("22"))], vec![], vec![tool("24", None, true), tool("25", None, true)]),
+            installed: vec![],
+            requests: vec![request("tools", Some("24")), request("platforms;android-23", None)],
+            expected: Ok(vec!["install tools 24 <- platforms;android-23", "install platforms;android-23 1"]),
+        },
+        Case {
+            name: "pin-below-minimum",
+            repository: sdk_repository(vec![platform(23, Some("22"))], vec![], vec![tool("21", None, true), tool("25", None, true)]),
+            installed: vec![],
+            requests: vec![request("tools", Some("21")), request("platforms;android-23", None)],
+            expected: Err("conflicting requirements:\ntools 22 is required by platforms;android-23 but 21 is requested"),
+        },
+        Case {
+            name: "pin-unavailable",
+            repository: sdk_repository(vec![], vec![], vec![tool("25", None, true)]),
+            installed: vec![],
+            requests: vec![request("tools", Some("30"))],
+            expected: Err("conflicting requirements:\ntools 30 is not available"),
+        },
+        Case {
+            name: "minimum-unavailable",
+            repository: sdk_repository(vec![platform(23, Some("26"))], vec![], vec![tool("25", None, true)]),
+            installed: vec![],
+            requests: vec![request("platforms;android-23", None)],
+            expected: Err("conflicting requirements:\ntools 26 is required by platforms;android-23 but the newest available revision is 25"),
+        },
+        Case {
+            name: "prefer-host",
+            repository: sdk_repository(vec![], vec![], vec![tool("25", None, true), tool("26", None, false)]),
+            installed: vec![],
+            requests: vec![request("tools", None)],
+            expected: Ok(vec!["install tools 25"]),
+        },
+        Case {
+            // tools 25 is chosen first for having an archive for this host, then replaced by
+            // the installed 26 the platform requires, whose requirements are the only ones left.
+            name: "replaced-candidate",
+            repository: sdk_repository(vec![platform(23, Some("26"))], vec![platform_tool("22")], vec![tool("25", Some("23"), true), tool("26", Some("20"), false)]),
+            installed: vec![("tools", "26")],
+            requests: vec![request("tools", None), request("platforms;android-23", None)],
+            expected: Ok(vec!["install platform-tools 22 <- tools", "keep tools 26 <- platforms;android-23", "install platforms;android-23 1"]),
+        },
+    ];
+
+    for case in &cases {
+        match (resolve(case), &case.expected) {
+            (Ok(steps), Ok(expected)) => { assert_eq!(*expected, steps, "{}", case.name); }
+            (Err(error), Err(expected)) => { assert!(error.starts_with(*expected), "{}: {}", case.name, error); }
+            (result, expected) => { panic!("{}: expected {:?}, got {:?}", case.name, expected, result); }
+        }
+    }
+}
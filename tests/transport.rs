@@ -0,0 +1,83 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use android_sdk_cli::error::Error;
+use android_sdk_cli::transport::{FileTransport, Request, Response, Transport};
+use common::BASE_URL;
+use hyper::status::StatusCode;
+use std::io::Read;
+
+fn body(response: Response) -> Vec<u8> {
+    let mut content = Vec::new();
+    let mut body = response.body;
+    body.read_to_end(&mut content).unwrap();
+    return content;
+}
+
+fn status(result: Result<Response, Error>) -> StatusCode {
+    match result {
+        Err(Error::Status { status, .. }) => status,
+        Ok(response) => response.status,
+        Err(e) => { panic!("unexpected error: {}", e); }
+    }
+}
+
+fn range(url: &str, offset: u64) -> Request {
+    Request {
+        range_from: Some(offset),
+        ..Request::new(url)
+    }
+}
+
+#[test]
+fn serve_from_memory() {
+    let transport = common::serving(BASE_URL);
+    let url = format!("{}/platform-tools_r23.zip", BASE_URL);
+    let content = common::fixture("platform-tools_r23.zip");
+
+    let response = transport.get(&Request::new(&url)).unwrap();
+    assert_eq!(StatusCode::Ok, response.status);
+    assert_eq!(Some(content.len() as u64), response.content_length);
+    let etag = response.etag.clone().unwrap();
+    assert_eq!(content, body(response));
+
+    let response = transport.get(&range(&url, 100)).unwrap();
+    assert_eq!(StatusCode::PartialContent, response.status);
+    assert_eq!(Some(100), response.range_start);
+    assert_eq!(&content[100..], &body(response)[..]);
+
+    let request = Request {
+        if_none_match: Some(etag),
+        ..Request::new(&url)
+    };
+    assert_eq!(StatusCode::NotModified, status(transport.get(&request)));
+    assert_eq!(StatusCode::RangeNotSatisfiable, status(transport.get(&range(&url, content.len() as u64))));
+    assert_eq!(StatusCode::NotFound, status(transport.get(&Request::new(&format!("{}/missing.zip", BASE_URL)))));
+}
+
+#[test]
+fn read_files() {
+    let url = format!("file://{}/tools_r25.zip", common::fixture_dir().display());
+    let content = common::fixture("tools_r25.zip");
+
+    let response = FileTransport.get(&Request::new(&url)).unwrap();
+    assert_eq!(StatusCode::Ok, response.status);
+    assert_eq!(Some(content.len() as u64), response.content_length);
+    assert_eq!(content, body(response));
+
+    let response = FileTransport.get(&range(&url, 100)).unwrap();
+    assert_eq!(StatusCode::PartialContent, response.status);
+    assert_eq!(Some(content.len() as u64 - 100), response.content_length);
+    assert_eq!(&content[100..], &body(response)[..]);
+
+    assert_eq!(StatusCode::RangeNotSatisfiable, status(FileTransport.get(&range(&url, content.len() as u64))));
+    for url in &[format!("file://{}/missing.zip", common::fixture_dir().display()), format!("{}/tools_r25.zip", BASE_URL)] {
+        match FileTransport.get(&Request::new(url)) {
+            Err(Error::Io(_)) => {}
+            Err(e) => { panic!("{}: unexpected error: {}", url, e); }
+            Ok(_) => { panic!("{}: unexpected response", url); }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+extern crate android_sdk_cli;
+extern crate hyper;
+
+mod common;
+
+use common::install_at;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn uninstall(root: &Path, args: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_android-sdk-cli")).arg("uninstall").arg("--sdk-root").arg(root).args(args).output().unwrap();
+}
+
+fn stderr(output: &Output) -> String {
+    return String::from_utf8_lossy(&output.stderr).into_owned();
+}
+
+/// Names of the directories left in `dir`, sorted.
+fn list(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    names.sort();
+    return names;
+}
+
+/// An SDK with tools depending on platform-tools.
+fn install_tools(root: &Path) {
+    install_at(root, "platform-tools", "Pkg.Revision=23\n");
+    install_at(root, "tools", "Pkg.Revision=25\nPlatform.MinPlatformToolsRev=20\n");
+}
+
+#[test]
+fn refuse_to_remove_dependency() {
+    let root = common::temp_dir("uninstall-refuse");
+    install_tools(&root);
+
+    let output = uninstall(&root, &["platform-tools"]);
+
+    assert!(!output.status.success());
+    assert_eq!("platform-tools is required by tools\nuse --force to uninstall anyway\n", stderr(&output));
+    assert!(root.join("platform-tools").is_dir());
+}
+
+#[test]
+fn remove_together_with_dependents() {
+    let root = common::temp_dir("uninstall-together");
+    install_tools(&root);
+
+    let output = uninstall(&root, &["platform-tools", "tools"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(vec![".android-sdk-cli.lock"], list(&root));
+}
+
+#[test]
+fn remove_one_of_two_providers() {
+    let root = common::temp_dir("uninstall-providers");
+    install_at(&root, "platforms/android-23", "Pkg.Revision=2\nAndroidVersion.ApiLevel=23\n");
+    install_at(&root, "platforms/android-6.0", "Pkg.Revision=2\nAndroidVersion.ApiLevel=23\n");
+    install_at(&root, "system-images/android-23/google_apis/x86", "Pkg.Revision=5\n");
+
+    // platforms/android-6.0 still provides API 23 to the system image.
+    let output = uninstall(&root, &["platforms;android-23"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(vec!["android-6.0"], list(&root.join("platforms")));
+
+    let output = uninstall(&root, &["platforms;android-23"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).starts_with("platforms;android-23 is required by system-images;android-23;google_apis;x86\n"), "{}", stderr(&output));
+    assert_eq!(vec!["android-6.0"], list(&root.join("platforms")));
+}
+
+#[test]
+fn force_removal() {
+    let root = common::temp_dir("uninstall-force");
+    install_tools(&root);
+
+    let output = uninstall(&root, &["--force", "platform-tools"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(vec![".android-sdk-cli.lock", "tools"], list(&root));
+}